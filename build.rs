@@ -0,0 +1,34 @@
+// Bakes every vault in resources/prefabs into the binary, so a new prefab file only needs dropping in there
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    let dir = Path::new(&env::var("CARGO_MANIFEST_DIR").unwrap()).join("resources/prefabs");
+    println!("cargo:rerun-if-changed={}", dir.display());
+
+    let mut vaults: Vec<_> = fs::read_dir(&dir)
+        .expect("resources/prefabs is missing")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+        .collect();
+    vaults.sort();
+
+    let entries: String = vaults
+        .iter()
+        .map(|path| {
+            println!("cargo:rerun-if-changed={}", path.display());
+            format!(
+                "    ({:?}, include_str!({:?})),\n",
+                path.file_stem().unwrap().to_string_lossy(),
+                path.display().to_string()
+            )
+        })
+        .collect();
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("vaults.rs");
+    fs::write(
+        out,
+        format!("const VAULT_DATA: &[(&str, &str)] = &[\n{}];\n", entries),
+    )
+    .unwrap();
+}
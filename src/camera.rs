@@ -1,5 +1,7 @@
 use crate::prelude::*;
+use serde::{Deserialize, Serialize};
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Camera {
     pub left_x: i32,
     pub right_x: i32,
@@ -0,0 +1,120 @@
+// Describes every level of a run, from the first forest to the level holding the teleportation crystal
+// This struct is reflected in campaign.ron, add or reorder levels there rather than in code
+
+use crate::prelude::*;
+use ron::de::from_str;
+use serde::Deserialize;
+
+// The architects a level can be laid out by, see map_builder for what each of them does
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub enum ArchitectKind {
+    Empty,
+    Rooms,
+    Bsp,
+    CellularAutomata,
+    DrunkardsWalk,
+    WaveFunctionCollapse(String), // Name of the sample to learn from
+}
+
+// The meta-builder steps run after the architect, in the order they are listed
+#[derive(Clone, Deserialize, Debug, PartialEq)]
+pub enum BuildStep {
+    CullUnreachable,
+    RoomDoors,
+    RoomSpawns,
+    AreaSpawns,
+    PlaceExitFurthest,
+    DungeonDecorations,
+    ForestDecorations,
+    CaveDecorations,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct CampaignLevel {
+    pub name: String, // Shown on the HUD
+    pub theme: String,
+    // One of these is picked at random, list an architect more than once to make it more likely
+    pub architects: Vec<ArchitectKind>,
+    pub steps: Vec<BuildStep>,
+    // Every level gets a shot at one of these vaults (named after their file in resources/prefabs)
+    // as long as the vault allows this depth and theme, an empty pool means no vault at all
+    pub prefabs: Vec<String>,
+    // Most spawn points the spawn steps hand out
+    pub spawn_budget: usize,
+    // The level with the crystal has no exit, finding the crystal wins the run
+    pub teleportation_crystal: bool,
+}
+
+#[derive(Clone, Deserialize, Debug)]
+pub struct Campaign {
+    pub levels: Vec<CampaignLevel>,
+}
+
+impl Campaign {
+    // The themes and vaults the levels name have to exist, see validate
+    pub fn load(themes: &ThemeLibrary) -> Result<Self, Vec<String>> {
+        let campaign: Self = from_str(&data_file("campaign.ron", CAMPAIGN_DATA))
+            .map_err(|e| vec![format!("Unable to parse campaign.ron: {}", e)])?;
+        let problems = campaign.validate(themes);
+        if problems.is_empty() {
+            Ok(campaign)
+        } else {
+            Err(problems)
+        }
+    }
+
+    // The campaign we were built with, for when a modded one is broken and the game only shows what is wrong
+    pub fn built_in() -> Self {
+        from_str(CAMPAIGN_DATA).expect("Unable to parse the built in campaign")
+    }
+
+    fn validate(&self, themes: &ThemeLibrary) -> Vec<String> {
+        let mut problems = Vec::new();
+        // Without the crystal at the bottom the player would walk off the end of the campaign
+        match self.levels.last() {
+            None => problems.push("The campaign needs at least one level".to_string()),
+            Some(last) if !last.teleportation_crystal => problems.push(
+                "The last level of the campaign must hold the teleportation crystal".to_string(),
+            ),
+            Some(_) => {}
+        }
+        let vaults: Vec<String> = vault_files().into_iter().map(|(name, _)| name).collect();
+        for (i, level) in self.levels.iter().enumerate() {
+            let name = format!("level {} ({})", i + 1, level.name);
+            if level.architects.is_empty() {
+                problems.push(format!("{}: needs at least one architect", name));
+            }
+            level
+                .architects
+                .iter()
+                .filter_map(|architect| match architect {
+                    ArchitectKind::WaveFunctionCollapse(sample) => Some(sample),
+                    _ => None,
+                })
+                .filter(|sample| sample_by_name(sample).is_none())
+                .for_each(|sample| {
+                    problems.push(format!("{}: there is no sample called {}", name, sample))
+                });
+            if !themes.has(&level.theme) {
+                problems.push(format!(
+                    "{}: there is no theme called {}",
+                    name, level.theme
+                ));
+            }
+            level
+                .prefabs
+                .iter()
+                .filter(|prefab| !vaults.contains(prefab))
+                .for_each(|prefab| {
+                    problems.push(format!("{}: there is no vault called {}", name, prefab))
+                });
+        }
+        problems
+    }
+
+    // Past the end of the campaign (a save from a longer one, say) the last level carries on
+    // validate makes sure there is at least one level
+    pub fn level(&self, map_level: u32) -> &CampaignLevel {
+        &self.levels[(map_level as usize).min(self.levels.len() - 1)]
+    }
+}
@@ -4,67 +4,140 @@ use std::collections::HashSet;
 // They dont have to explicilty have any functionality
 
 pub use crate::prelude::*; // Bring in our prelude stuff
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Render {
     pub color: ColorPair,
     pub glyph: FontCharType,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Health {
     pub current: i32,
     pub max: i32,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct Damage(pub i32);
+// Rolled anew for every hit
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Damage(pub DiceType);
+
+// Added to the to-hit roll of the attacker
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Accuracy(pub i32);
+
+// Taken off the to-hit roll of whoever attacks
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Evasion(pub i32);
 
 // Items can inflict damage but they aren't monsters so we need a way to indentify that an item is a weapon
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Weapon;
 
-#[derive(Clone, PartialEq)]
+// Attacks anything within range tiles that it has a clear line of fire to, on monsters and weapons
+// It fires at most once every cooldown turns
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Ranged {
+    pub range: i32,
+    pub cooldown: i32,
+}
+
+// Monster turns left before a Ranged monster or weapon can fire again, see the cooldowns system
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cooldown(pub i32);
+
+// Shots left in a ranged weapon, once they are gone it's only good for hitting things up close
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Ammo(pub i32);
+
+// Takes damage off every hit, on creatures themselves and on the armour they wear
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Defence(pub i32);
+
+// Worn rather than used, like weapons only one of them can be carried at a time
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Armour;
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Name(pub String);
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Player {
     pub wait_count: u8,
     pub map_level: u32,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+// How far the drone has come along, every level takes XP_PER_LEVEL more experience than the last
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Progress {
+    pub level: i32,
+    pub xp: i32,
+}
+
+const XP_PER_LEVEL: i32 = 10;
+
+impl Progress {
+    pub fn new() -> Self {
+        Self { level: 1, xp: 0 }
+    }
+
+    pub fn xp_to_next_level(&self) -> i32 {
+        self.level * XP_PER_LEVEL
+    }
+
+    pub fn can_level_up(&self) -> bool {
+        self.xp >= self.xp_to_next_level()
+    }
+}
+
+// Experience for whoever lands the killing blow
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GrantsXp(pub i32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Enemy; // Enemy tag component
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct MovingRandomly; // Tag component as the others above
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ChasingPlayer;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct MoveEveryOther(pub bool); // Tag component, bool is used for flagging if it can move or not
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Item;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct TelerportationCrystal;
 
+// Sets off its Provides effects on whoever ends their turn on top of it
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hazard;
+
+// Replaces the Point of everything left behind on a level the player isn't on, so no system sees it
+// until the player comes back and it gets its Point back
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OtherLevelPosition {
+    pub pos: Point,
+    pub map_level: u32,
+}
+
 // Message component
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct WantsToMove {
     pub entity: Entity,
     pub destination: Point,
 }
 
 // Message compponent
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct WantsToLog {
     pub log_entry: LogEntry,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct WantsToAttack {
     pub attacker: Entity,
     pub victim: Entity,
@@ -72,13 +145,24 @@ pub struct WantsToAttack {
 
 // Message of intent for this as well, potentially we can let monsters use items if they want...
 // what is the monster is below certain health and has a potion close to it, perhaps go for the potion instead
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ActivateItem {
     pub used_by: Entity,
     pub item: Entity,
+    pub target: Option<Point>, // The tile picked for items with an Area effect
 }
 
-#[derive(Clone, Debug, PartialEq)]
+// Message component, user is whoever set the effect off, area effects are centered on them
+// victim is who the user hit when the effect comes from an attack, target the tile picked for an Area effect
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ApplyEffect {
+    pub user: Entity,
+    pub victim: Option<Entity>,
+    pub target: Option<Point>,
+    pub effect: Effect,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct FieldOfView {
     pub visible_tiles: HashSet<Point>,
     pub radius: i32,
@@ -103,20 +187,66 @@ impl FieldOfView {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct ProvidesHealing {
-    pub amount: i32,
+// The effects an item sets off when it is used, in the order they are listed in template.ron
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Provides(pub Vec<Effect>);
+
+// Effects that come along with every attack that lands, from a monster or from a carried weapon
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct OnHit(pub Vec<Effect>);
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct Carried(pub Entity);
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub turns: i32, // Monster turns left
+    pub stacks: i32,
+    // Flips every player turn for statuses that act every other turn, Hasted uses it for the extra turn
+    #[serde(default)]
+    pub phase: bool,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct ProvidesDungeonMap;
+// Lasting effects, every creature has one of these even when there is nothing in it
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Statuses(pub Vec<StatusEffect>);
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct ProvidesStun;
+impl Statuses {
+    pub fn get(&self, kind: StatusKind) -> Option<&StatusEffect> {
+        self.0.iter().find(|status| status.kind == kind)
+    }
 
-#[derive(Clone, PartialEq)]
-pub struct Carried(pub Entity);
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.get(kind).is_some()
+    }
 
-// Lasting effect
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct Stunned(pub i32);
+    // Adds the status following its stacking rules, true when it wasn't there before
+    pub fn apply(&mut self, kind: StatusKind, duration: i32) -> bool {
+        if let Some(opposite) = kind.opposite() {
+            self.0.retain(|status| status.kind != opposite);
+        }
+        match self.0.iter_mut().find(|status| status.kind == kind) {
+            Some(status) => {
+                match kind.stacking() {
+                    Stacking::Refresh => status.turns = status.turns.max(duration),
+                    Stacking::Extend => status.turns += duration,
+                    Stacking::Intensify => {
+                        status.turns = status.turns.max(duration);
+                        status.stacks += 1;
+                    }
+                }
+                false
+            }
+            None => {
+                self.0.push(StatusEffect {
+                    kind,
+                    turns: duration,
+                    stacks: 1,
+                    phase: false,
+                });
+                true
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+// Game data baked into the binary at compile time, so the game also runs where there are no files
+// to read (the WebAssembly build). Dropping a file with the same name into mods/ replaces the built in one
+
+use crate::prelude::*;
+use std::borrow::Cow;
+
+const MOD_DIR: &str = "mods";
+
+pub const TEMPLATE_DATA: &str = include_str!("../resources/template.ron");
+pub const CAMPAIGN_DATA: &str = include_str!("../resources/campaign.ron");
+pub const THEME_DATA: &str = include_str!("../resources/themes.ron");
+
+// Every vault that ships with the game, named after its file in resources/prefabs (see build.rs)
+include!(concat!(env!("OUT_DIR"), "/vaults.rs"));
+
+// The modded copy of a data file if there is one, otherwise the one we were built with
+// Debug builds read resources/ from disk first, that way edits show up without a rebuild (see TemplateWatcher)
+pub fn data_file(name: &str, embedded: &'static str) -> Cow<'static, str> {
+    let mut paths = vec![format!("{}/{}", MOD_DIR, name)];
+    if cfg!(debug_assertions) {
+        paths.push(format!("resources/{}", name));
+    }
+    paths
+        .iter()
+        .find_map(|path| std::fs::read_to_string(path).ok())
+        .map_or(Cow::Borrowed(embedded), Cow::Owned)
+}
+
+// Name and contents of every vault, mods/prefabs can replace a built in vault or add new ones
+// Debug builds read resources/prefabs from disk first, same as data_file
+// A file we couldn't read keeps its name but Err says why, see VaultLibrary::load
+// Sorted by name so the same seed always sees the same list
+pub fn vault_files() -> Vec<(String, Result<Cow<'static, str>, String>)> {
+    let mut files: Vec<(String, Result<Cow<'static, str>, String>)> = VAULT_DATA
+        .iter()
+        .map(|(name, contents)| (name.to_string(), Ok(Cow::Borrowed(*contents))))
+        .collect();
+
+    let mut dirs = Vec::new();
+    if cfg!(debug_assertions) {
+        dirs.push("resources/prefabs".to_string());
+    }
+    dirs.push(format!("{}/prefabs", MOD_DIR));
+    for entries in dirs.iter().filter_map(|dir| std::fs::read_dir(dir).ok()) {
+        let paths = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "ron"));
+        for path in paths {
+            let name = path
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            let contents = std::fs::read_to_string(&path)
+                .map(Cow::Owned)
+                .map_err(|e| format!("failed reading {}: {}", path.display(), e));
+            files.retain(|(other, _)| *other != name);
+            files.push((name, contents));
+        }
+    }
+
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    files
+}
+
+// Parses the data files once for the whole run, broken templates, vaults or themes leave us without them
+// and the game shows what is wrong instead (see data_problems)
+pub fn insert_game_data(resources: &mut Resources) {
+    let templates = Templates::load().unwrap_or_default();
+    let vaults = VaultLibrary::load(&templates).unwrap_or_default();
+    let themes = ThemeLibrary::load().unwrap_or_default();
+    resources.insert(Campaign::load(&themes).unwrap_or_else(|_| Campaign::built_in()));
+    resources.insert(templates);
+    resources.insert(vaults);
+    resources.insert(themes);
+}
+
+// Everything wrong with the templates, vaults, themes and campaign, empty when the game is good to go
+pub fn data_problems() -> Vec<String> {
+    let mut problems = match Templates::load() {
+        // The vaults refer to the templates, no use checking them against broken ones
+        Err(problems) => problems,
+        Ok(templates) => VaultLibrary::load(&templates).err().unwrap_or_default(),
+    };
+    // Same goes for the campaign and the themes
+    match ThemeLibrary::load() {
+        Err(theme_problems) => problems.extend(theme_problems),
+        Ok(themes) => problems.extend(Campaign::load(&themes).err().unwrap_or_default()),
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_data_has_no_problems() {
+        assert_eq!(data_problems(), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,192 @@
+// Everything an item, a hazard or a monster's attack can do, written out in template.ron as for example
+// Heal(amount: 4, target: User) or Status(status: Stunned, duration: 6, target: Around(radius: 1, kind: Foes))
+// Whatever provides the effect only sends an ApplyEffect message, the apply_effects system does the rest
+
+use serde::{Deserialize, Serialize};
+
+// Which of the entities around the user an area effect hits
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TargetKind {
+    // Monsters when the player is the user, the player when a monster is
+    Foes,
+    Everyone,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Target {
+    User,
+    // Whoever the user just hit, only makes sense for on_hit effects
+    Victim,
+    // Everyone of the kind within radius tiles of the user, not counting the user
+    // A radius of 1 is the four tiles next to the user
+    Around { radius: i32, kind: TargetKind },
+    // Everyone of the kind within radius tiles of the tile the user picked, the user as well if it is in there
+    // Only items can have it, using one asks for the tile first. A radius of 0 is just that tile
+    Area { radius: i32, kind: TargetKind },
+}
+
+// Lasting effects, see the statuses system for what each of them does every turn
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatusKind {
+    Stunned,      // No moving or attacking
+    Poisoned,     // 1 damage a turn for every dose
+    Regenerating, // Heals 1 a turn
+    Hasted,       // Twice as fast, see chasing and end_turn
+    Slowed,       // Only moves every other turn
+    Blinded,      // Sees no further than the next tile
+    Confused,     // Stumbles in a random direction
+    Burning,      // 2 damage a turn
+}
+
+// What happens when a status lands on someone who already has it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stacking {
+    Refresh,   // The longer of the two durations wins
+    Extend,    // The durations add up
+    Intensify, // One more dose, the longer of the two durations wins
+}
+
+impl StatusKind {
+    pub fn stacking(self) -> Stacking {
+        match self {
+            StatusKind::Poisoned => Stacking::Intensify,
+            StatusKind::Regenerating => Stacking::Extend,
+            _ => Stacking::Refresh,
+        }
+    }
+
+    // Hasted and Slowed cancel each other out
+    pub fn opposite(self) -> Option<StatusKind> {
+        match self {
+            StatusKind::Hasted => Some(StatusKind::Slowed),
+            StatusKind::Slowed => Some(StatusKind::Hasted),
+            _ => None,
+        }
+    }
+
+    // Damage dealt at the end of every monster turn, healing when it is negative
+    pub fn damage_per_turn(self, stacks: i32) -> i32 {
+        match self {
+            StatusKind::Poisoned => stacks,
+            StatusKind::Burning => 2,
+            StatusKind::Regenerating => -1,
+            _ => 0,
+        }
+    }
+
+    // Lower case since it ends up in the middle of log messages
+    pub fn name(self) -> &'static str {
+        match self {
+            StatusKind::Stunned => "stunned",
+            StatusKind::Poisoned => "poisoned",
+            StatusKind::Regenerating => "regenerating",
+            StatusKind::Hasted => "hasted",
+            StatusKind::Slowed => "slowed",
+            StatusKind::Blinded => "blinded",
+            StatusKind::Confused => "confused",
+            StatusKind::Burning => "burning",
+        }
+    }
+}
+
+impl Target {
+    // Who it lands on, in words for the inventory screen
+    pub fn describe(&self) -> String {
+        let kind = |kind: TargetKind| match kind {
+            TargetKind::Foes => "foes",
+            TargetKind::Everyone => "everyone",
+        };
+        let tiles = |radius: i32| match radius {
+            1 => "1 tile".to_string(),
+            radius => format!("{} tiles", radius),
+        };
+        match *self {
+            Target::User => "you".to_string(),
+            Target::Victim => "whoever is hit".to_string(),
+            Target::Around { radius, kind: k } => {
+                format!("{} within {} of you", kind(k), tiles(radius))
+            }
+            Target::Area { radius: 0, kind: k } => format!("{} on the picked tile", kind(k)),
+            Target::Area { radius, kind: k } => {
+                format!("{} within {} of the picked tile", kind(k), tiles(radius))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Effect {
+    Heal {
+        amount: i32,
+        target: Target,
+    },
+    Damage {
+        amount: i32,
+        target: Target,
+    },
+    // Duration is counted in monster turns
+    Status {
+        status: StatusKind,
+        duration: i32,
+        target: Target,
+    },
+    RevealMap,
+}
+
+impl Effect {
+    pub fn target(&self) -> Target {
+        match self {
+            Effect::Heal { target, .. }
+            | Effect::Damage { target, .. }
+            | Effect::Status { target, .. } => *target,
+            Effect::RevealMap => Target::User,
+        }
+    }
+
+    // What it does, in words for the inventory screen
+    pub fn describe(&self) -> String {
+        match *self {
+            Effect::Heal { amount, target } => {
+                format!("Heals {} for {} hp", target.describe(), amount)
+            }
+            Effect::Damage { amount, target } => {
+                format!("{} damage to {}", amount, target.describe())
+            }
+            Effect::Status {
+                status,
+                duration,
+                target,
+            } => format!(
+                "Leaves {} {} for {} turns",
+                target.describe(),
+                status.name(),
+                duration
+            ),
+            Effect::RevealMap => "Reveals the whole level".to_string(),
+        }
+    }
+
+    // Radius around the picked tile for effects that need one picked, None for the rest
+    pub fn area(&self) -> Option<i32> {
+        match self.target() {
+            Target::Area { radius, .. } => Some(radius),
+            _ => None,
+        }
+    }
+
+    // Something that would make the effect do nothing, None when it is fine
+    pub fn problem(&self) -> Option<String> {
+        let problem = match *self {
+            Effect::Heal { amount, .. } | Effect::Damage { amount, .. } if amount < 1 => {
+                "amount has to be at least 1"
+            }
+            Effect::Status { duration, .. } if duration < 1 => "duration has to be at least 1",
+            _ => match self.target() {
+                Target::Around { radius, .. } if radius < 1 => "radius has to be at least 1",
+                Target::Area { radius, .. } if radius < 0 => "radius can't be negative",
+                _ => return None,
+            },
+        };
+        Some(format!("{:?}: {}", self, problem))
+    }
+}
@@ -1,17 +1,21 @@
 use std::collections::LinkedList;
 
 use crate::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct LogEntry {
     pub message: String,
     pub color: ColorPair,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct EventLog {
     pub messages: LinkedList<LogEntry>,
 }
 
+const MAX_LOG_LENGTH: usize = 6;
+
 impl EventLog {
     pub fn new() -> Self {
         Self {
@@ -19,6 +23,15 @@ impl EventLog {
         }
     }
 
+    // Newest entry first, the oldest one falls off once the log is full
+    pub fn push(&mut self, log_entry: LogEntry) {
+        self.messages.push_front(log_entry);
+        if self.messages.len() > MAX_LOG_LENGTH {
+            // clamp size
+            self.messages.pop_back();
+        }
+    }
+
     pub fn log(commands: &mut CommandBuffer, msg: String) {
         commands.push((
             (),
@@ -0,0 +1,66 @@
+// What the drone carries around, in the order the number keys and the inventory screen list it
+
+use crate::prelude::*;
+
+pub fn carried_items(ecs: &impl EntityStore, owner: Entity) -> Vec<Entity> {
+    <(Entity, &Item, &Carried)>::query()
+        .iter(ecs)
+        .filter(|(_, _, carried)| carried.0 == owner)
+        .map(|(entity, _, _)| *entity)
+        .collect()
+}
+
+// One line for everything the item does, for the inventory screen
+pub fn describe_item(ecs: &World, item: Entity) -> Vec<String> {
+    let entry = match ecs.entry_ref(item) {
+        Ok(entry) => entry,
+        Err(_) => return Vec::new(),
+    };
+    let mut lines = Vec::new();
+    if let Ok(damage) = entry.get_component::<Damage>() {
+        lines.push(format!("Damage {}", dice(damage.0)));
+    }
+    if let Ok(accuracy) = entry.get_component::<Accuracy>() {
+        lines.push(format!("Accuracy {:+}", accuracy.0));
+    }
+    if let Ok(ranged) = entry.get_component::<Ranged>() {
+        lines.push(match ranged.cooldown {
+            0 | 1 => format!("Fires up to {} tiles", ranged.range),
+            n => format!("Fires up to {} tiles, once every {} turns", ranged.range, n),
+        });
+    }
+    if let Ok(ammo) = entry.get_component::<Ammo>() {
+        lines.push(format!("{} shots left", ammo.0));
+    }
+    if let Ok(cooldown) = entry.get_component::<Cooldown>() {
+        lines.push(format!("Ready to fire in {} turns", cooldown.0));
+    }
+    if let Ok(defence) = entry.get_component::<Defence>() {
+        lines.push(format!("Defence {}", defence.0));
+    }
+    if let Ok(evasion) = entry.get_component::<Evasion>() {
+        lines.push(format!("Evasion {:+}", evasion.0));
+    }
+    if let Ok(on_hit) = entry.get_component::<OnHit>() {
+        on_hit
+            .0
+            .iter()
+            .for_each(|effect| lines.push(format!("On hit: {}", effect.describe())));
+    }
+    if let Ok(provides) = entry.get_component::<Provides>() {
+        provides
+            .0
+            .iter()
+            .for_each(|effect| lines.push(effect.describe()));
+    }
+    lines
+}
+
+// Back to the notation template.ron uses, flat damage is just the number
+fn dice(dice: DiceType) -> String {
+    match (dice.n_dice, dice.bonus) {
+        (0, bonus) => bonus.to_string(),
+        (n, 0) => format!("{}d{}", n, dice.die_type),
+        (n, bonus) => format!("{}d{}{:+}", n, dice.die_type, bonus),
+    }
+}
@@ -1,14 +1,24 @@
-use std::collections::HashSet;
-
 // Brings modules into scope
 mod camera;
+mod campaign;
 mod components;
+mod data;
+mod effects;
 mod event_log;
+mod inventory;
 mod map;
 mod map_builder;
+mod player_action;
+mod replay;
+mod save_game;
+mod seed;
+mod simulation;
 mod spawner;
 mod systems;
+mod targeting;
+mod template_watcher;
 mod turn_state;
+mod visited_levels;
 
 mod prelude {
     pub use bracket_lib::prelude::*;
@@ -19,62 +29,128 @@ mod prelude {
     pub const SCREEN_HEIGHT: i32 = 40;
     pub const DISPLAY_WIDTH: i32 = SCREEN_WIDTH / 2;
     pub const DISPLAY_HEIGHT: i32 = SCREEN_HEIGHT / 2;
+    // Size of the levels we generate, the camera scrolls over maps bigger than the display
+    pub const MAP_WIDTH: i32 = 50;
+    pub const MAP_HEIGHT: i32 = 40;
     pub use crate::camera::*;
+    pub use crate::campaign::*;
     pub use crate::components::*;
+    pub use crate::data::*;
+    pub use crate::effects::*;
     pub use crate::event_log::*;
+    pub use crate::inventory::*;
     pub use crate::map::*;
     pub use crate::map_builder::*;
+    pub use crate::player_action::*;
+    pub use crate::replay::*;
+    pub use crate::save_game::*;
+    pub use crate::seed::*;
+    pub use crate::simulation::*;
     pub use crate::spawner::*;
     pub use crate::systems::*;
+    pub use crate::targeting::*;
+    pub use crate::template_watcher::*;
     pub use crate::turn_state::*;
+    pub use crate::visited_levels::*;
 }
 
 use prelude::*;
 
 struct State {
-    // Collection of entities + components
-    ecs: World,
-    resources: Resources, // Shared data
-    // Various system collections
-    input_systems: Schedule,
-    player_systems: Schedule,
-    monster_systems: Schedule,
+    // The game itself, we only add input capture and rendering on top of it
+    sim: Simulation,
+    render_systems: Schedule,
+    resumed: bool, // Did we pick up a run from the save file?
+    // While watching a replay file, along with how many of its actions we've played so far
+    playback: Option<(Replay, usize)>,
+    // Whatever is wrong with the templates or vaults, shown instead of the game until it has been fixed
+    data_problems: Vec<String>,
+    // Debug builds only, reloads template.ron whenever it is saved
+    template_watcher: Option<TemplateWatcher>,
+    // Where the mouse was last frame, the targeting cursor only follows it when it moves
+    last_mouse: Point,
+    // The item picked on the inventory screen
+    inventory_cursor: usize,
 }
 
 impl State {
-    fn new() -> Self {
-        let mut ecs = World::default();
-        let mut resources = Resources::default();
-        // Creates a map builder from which we grab our map
-        let mut rng = RandomNumberGenerator::new();
-        let mut map_builder = MapBuilder::new(&mut rng, 0);
-        spawn_player(&mut ecs, map_builder.player_start);
-        // For the love of god, SEAL THE EXITS - krieger
-        let exit_idx = map_builder
-            .map
-            .point2d_to_index(map_builder.teleportation_crystal_start);
-        map_builder.map.tiles[exit_idx] = TileType::Exit;
-        spawn_level(
-            &mut ecs,
-            &mut rng,
-            0,
-            &map_builder.monster_spawns,
-            &map_builder.guaranteed_monster_spawns,
-        );
-
-        // Inject our map and camera as resources (since this is what is shared in our program)
-        resources.insert(map_builder.map);
-        resources.insert(Camera::new(map_builder.player_start));
-        resources.insert(TurnState::Menu);
-        resources.insert(map_builder.theme);
-        resources.insert(EventLog::new());
+    fn new(seed: RunSeed) -> Self {
+        let mut sim = Simulation::new(seed);
+        sim.resources.insert(TurnState::Menu);
+        Self {
+            sim,
+            render_systems: build_render_scheduler(),
+            resumed: false,
+            playback: None,
+            data_problems: Vec::new(),
+            template_watcher: None,
+            last_mouse: Point::zero(),
+            inventory_cursor: 0,
+        }
+    }
 
+    // Rebuilds the run the replay was recorded from, its actions are fed in one key press at a time
+    fn replay(replay: Replay) -> Self {
         Self {
-            ecs,
-            resources,
-            input_systems: build_input_scheduler(),
-            player_systems: build_player_scheduler(),
-            monster_systems: build_monster_scheduler(),
+            sim: Simulation::new(replay.seed),
+            render_systems: build_render_scheduler(),
+            resumed: false,
+            playback: Some((replay, 0)),
+            data_problems: Vec::new(),
+            template_watcher: None,
+            last_mouse: Point::zero(),
+            inventory_cursor: 0,
+        }
+    }
+
+    // Picks up where the last run left off, if there is a save file to read
+    // Err says why the save file couldn't be read
+    fn load() -> Result<Option<Self>, String> {
+        if !has_save() {
+            return Ok(None);
+        }
+        match load_game() {
+            Ok((ecs, mut resources)) => {
+                resources.insert(TurnState::Menu);
+                Ok(Some(Self {
+                    sim: Simulation::from_parts(ecs, resources),
+                    render_systems: build_render_scheduler(),
+                    resumed: true,
+                    playback: None,
+                    data_problems: Vec::new(),
+                    template_watcher: None,
+                    last_mouse: Point::zero(),
+                    inventory_cursor: 0,
+                }))
+            }
+            Err(e) => Err(format!("Failed loading the save file: {}", e)),
+        }
+    }
+
+    // Nobody reads stdout while the window is up, so problems with the files go in the event log
+    fn report(&self, message: String) {
+        self.sim
+            .resources
+            .get_mut::<EventLog>()
+            .unwrap()
+            .push(LogEntry {
+                message,
+                color: ColorPair::new(RED, BLACK),
+            });
+    }
+
+    fn autosave(&self) {
+        if let Err(e) = save_game(&self.sim.ecs, &self.sim.resources) {
+            self.report(format!("Failed saving the game: {}", e));
+        }
+        self.write_replay();
+    }
+
+    // Keep the replay file up to date so it can be attached to a bug report at any point
+    fn write_replay(&self) {
+        let replay = self.sim.resources.get::<Replay>().unwrap();
+        if let Err(e) = replay.write(REPLAY_FILE) {
+            self.report(format!("Failed writing {}: {}", REPLAY_FILE, e));
         }
     }
 
@@ -82,7 +158,12 @@ impl State {
         ctx.set_active_console(2);
 
         ctx.print_centered(20, "DRONELIKE");
-        ctx.print_centered(23, "PRESS ANY BUTTON TO CRASH LAND ON A MYSTICAL PLANET");
+        if self.resumed {
+            ctx.print_centered(23, "PRESS ANY BUTTON TO RESUME YOUR DRONE'S JOURNEY");
+            ctx.print_centered(25, "PRESS N TO SEND IN A NEW DRONE");
+        } else {
+            ctx.print_centered(23, "PRESS ANY BUTTON TO CRASH LAND ON A MYSTICAL PLANET");
+        }
         ctx.print_right(
             SCREEN_WIDTH * 2,
             (SCREEN_HEIGHT * 2) - 3,
@@ -95,40 +176,19 @@ impl State {
         );
 
         // Check if the user has pressed any key
-        if let Some(_) = ctx.key {
-            self.resources.insert(TurnState::AwaitingInput);
+        match ctx.key {
+            Some(VirtualKeyCode::N) if self.resumed => self.reset_game_state(),
+            Some(_) => self.sim.resources.insert(TurnState::AwaitingInput),
+            None => {}
         }
     }
 
     fn reset_game_state(&mut self) {
         // Reset legion stuff and other variables!
-        self.ecs = World::default();
-        self.resources = Resources::default();
-        let mut rng = RandomNumberGenerator::new();
-        let mut map_builder = MapBuilder::new(&mut rng, 0);
-        // Spawn in entities
-        spawn_player(&mut self.ecs, map_builder.player_start);
-        // For the love of god, SEAL THE EXITS - krieger
-        // spawn_telerportation_crystal(&mut self.ecs, map_builder.teleportation_crystal_start);
-        let exit_idx = map_builder
-            .map
-            .point2d_to_index(map_builder.teleportation_crystal_start);
-        map_builder.map.tiles[exit_idx] = TileType::Exit;
-        // Spawn monsters and items
-        spawn_level(
-            &mut self.ecs,
-            &mut rng,
-            0,
-            &map_builder.monster_spawns,
-            &map_builder.guaranteed_monster_spawns,
-        );
-
-        // Insert resources into ecs system
-        self.resources.insert(map_builder.map);
-        self.resources.insert(Camera::new(map_builder.player_start));
-        self.resources.insert(TurnState::AwaitingInput);
-        self.resources.insert(map_builder.theme);
-        self.resources.insert(EventLog::new());
+        // A new drone gets a new seed
+        self.resumed = false;
+        self.playback = None;
+        self.sim = Simulation::new(RunSeed::random());
     }
 
     fn game_over(&mut self, ctx: &mut BTerm) {
@@ -155,110 +215,205 @@ impl State {
         }
     }
 
-    fn victory(&mut self, ctx: &mut BTerm) {
+    fn broken_data(&mut self, ctx: &mut BTerm) {
         ctx.set_active_console(2);
-        ctx.print_color_centered(2, GREEN, BLACK, "You have won!");
         ctx.print_color_centered(
-            4,
-            WHITE,
+            2,
+            RED,
             BLACK,
-            "The drone could teleport home! The drone can now drone forever!!",
+            "The drone can't take off, the game data has problems:",
         );
-        ctx.print_color_centered(7, GREEN, BLACK, "Press R to help another drone");
-
-        // Check for reset input
-        if let Some(VirtualKeyCode::R) = ctx.key {
-            self.reset_game_state();
+        let mut y = 5;
+        for problem in self.data_problems.iter() {
+            ctx.print_color(2, y, WHITE, BLACK, problem);
+            y += 1;
         }
+        ctx.print_color_centered(y + 2, YELLOW, BLACK, "Fix them and start the game again");
     }
 
-    fn advance_level(&mut self) {
-        // Get the player entity id
-        let player_entity = *<Entity>::query()
+    fn level_up(&mut self, ctx: &mut BTerm) {
+        ctx.set_active_console(2);
+        let level = <&Progress>::query()
             .filter(component::<Player>())
-            .iter(&mut self.ecs)
-            .nth(0)
+            .iter(&self.sim.ecs)
+            .map(|progress| progress.level + 1)
+            .next()
+            .unwrap_or(1);
+        ctx.print_color_centered(
+            2,
+            GOLD,
+            BLACK,
+            format!("The drone reached level {}!", level),
+        );
+        ctx.print_color_centered(4, WHITE, BLACK, "Pick an upgrade");
+        ctx.print_color_centered(7, WHITE, BLACK, "1. A sturdier hull, 5 more max health");
+        ctx.print_color_centered(8, WHITE, BLACK, "2. Sharper rotors, 1 more damage");
+        ctx.print_color_centered(9, WHITE, BLACK, "3. Better optics, see 2 tiles further");
+
+        if let Some(action) = ctx.key.and_then(PlayerAction::level_up_from_key) {
+            if self.sim.play_turn(action) == TurnState::AwaitingInput {
+                self.autosave();
+            }
+        }
+    }
+
+    // Save whenever a turn has played out, that way quitting the game
+    // at any point only loses the turn in progress
+    fn play(&mut self, action: PlayerAction) {
+        match self.sim.play_turn(action) {
+            TurnState::AwaitingInput => self.autosave(),
+            TurnState::GameOver | TurnState::Victory => {
+                if let Err(e) = delete_save() {
+                    self.report(format!("Failed removing the save file: {}", e));
+                }
+                self.write_replay();
+            }
+            _ => {}
+        }
+    }
+
+    // Moves the cursor with the keys or the mouse until the player picks a tile or changes their mind
+    fn targeting(&mut self, ctx: &mut BTerm) {
+        let mut targeting = self
+            .sim
+            .resources
+            .get::<Option<Targeting>>()
+            .unwrap()
             .unwrap();
+        let camera = self.sim.resources.get::<Camera>().unwrap().clone();
+        let mouse = *self.sim.resources.get::<Point>().unwrap();
+        let mouse_tile = mouse + Point::new(camera.left_x, camera.top_y);
+        if mouse != self.last_mouse {
+            targeting.cursor = mouse_tile;
+        }
 
-        // Create a Set of entities to not kill, insert player
-        let mut entities_to_keep = HashSet::new();
-        entities_to_keep.insert(player_entity);
-
-        // Make sure all the carried items are kept to next level
-        <(Entity, &Carried)>::query()
-            .iter(&mut self.ecs)
-            .filter(|(_, carry)| carry.0 == player_entity)
-            .map(|(e, _)| *e)
-            .for_each(|e| {
-                entities_to_keep.insert(e);
-            });
+        let fire = match ctx.key {
+            Some(VirtualKeyCode::Escape) => {
+                self.sim.stop_targeting();
+                return;
+            }
+            Some(VirtualKeyCode::Left | VirtualKeyCode::A) => {
+                targeting.cursor += Point::new(-1, 0);
+                false
+            }
+            Some(VirtualKeyCode::Right | VirtualKeyCode::D) => {
+                targeting.cursor += Point::new(1, 0);
+                false
+            }
+            Some(VirtualKeyCode::Up | VirtualKeyCode::W) => {
+                targeting.cursor += Point::new(0, -1);
+                false
+            }
+            Some(VirtualKeyCode::Down | VirtualKeyCode::S) => {
+                targeting.cursor += Point::new(0, 1);
+                false
+            }
+            Some(VirtualKeyCode::Tab) => {
+                targeting.next_target(&self.sim.ecs);
+                false
+            }
+            Some(VirtualKeyCode::F | VirtualKeyCode::Return) => true,
+            _ if ctx.left_click => {
+                targeting.cursor = mouse_tile;
+                true
+            }
+            _ => false,
+        };
+        self.sim.resources.insert(Some(targeting));
+
+        // Only tiles that can be picked, target_render tells what is wrong with the rest
+        let map = self.sim.resources.get::<Map>().unwrap().clone();
+        if fire && targeting.tiles(&self.sim.ecs, &map).is_ok() {
+            self.play(targeting.action());
+        }
+    }
+
+    // Everything the drone carries and what it does, pick one with the arrows to use or drop it
+    fn inventory(&mut self, ctx: &mut BTerm) {
+        ctx.set_active_console(2);
+        let player = <Entity>::query()
+            .filter(component::<Player>())
+            .iter(&self.sim.ecs)
+            .copied()
+            .next()
+            .unwrap();
+        let items = carried_items(&self.sim.ecs, player);
+        self.inventory_cursor = usize::min(self.inventory_cursor, items.len().saturating_sub(1));
 
-        // A much more effiecent way of performing multiple commands to the ECS system
-        // is through this method
-        let mut command_buffer = CommandBuffer::new(&mut self.ecs);
-        for e in Entity::query().iter(&self.ecs) {
-            // Will iterate over EVERY entity in the world
-            // Do we keep it or remove it?
-            if !entities_to_keep.contains(e) {
-                command_buffer.remove(*e);
+        ctx.print_color_centered(2, YELLOW, BLACK, "Inventory");
+        if items.is_empty() {
+            ctx.print_color_centered(5, WHITE, BLACK, "The drone isn't carrying anything");
+        }
+        let mut y = 5;
+        for (i, item) in items.iter().enumerate() {
+            let entry = self.sim.ecs.entry_ref(*item).unwrap();
+            let name = entry.get_component::<Name>().map_or("", |name| &name.0);
+            // Weapons and armour are worn as soon as they are picked up
+            let worn =
+                entry.get_component::<Weapon>().is_ok() || entry.get_component::<Armour>().is_ok();
+            let (marker, color) = match i == self.inventory_cursor {
+                true => (">", YELLOW),
+                false => (" ", WHITE),
+            };
+            let text = format!(
+                "{} {}. {}{}",
+                marker,
+                i + 1,
+                name,
+                if worn { " (worn)" } else { "" }
+            );
+            ctx.print_color(4, y, color, BLACK, text);
+            y += 1;
+            for line in describe_item(&self.sim.ecs, *item) {
+                ctx.print_color(9, y, GRAY, BLACK, line);
+                y += 1;
             }
+            y += 1;
         }
-        command_buffer.flush(&mut self.ecs);
-
-        // Mark field of view as dirty
-        // Making it not retain to the next level
-        // Notice the iter_mut(...) here since we modify the fov component
-        <&mut FieldOfView>::query()
-            .iter_mut(&mut self.ecs)
-            .for_each(|fov| fov.is_dirty = true);
-
-        // Create a map just like we've done in other functions before
-        let mut rng = RandomNumberGenerator::new();
-        // Get the player and thus map level
-        let mut mb = MapBuilder::new(
-            &mut rng,
-            self.ecs
-                .entry_ref(player_entity)
-                .unwrap()
-                .get_component::<Player>()
-                .unwrap()
-                .map_level
-                + 1,
+        ctx.print_color_centered(
+            SCREEN_HEIGHT * 2 - 3,
+            WHITE,
+            BLACK,
+            "Up and down to pick an item. U or Enter to use it, D to drop it. Escape or I to close",
         );
 
-        // Calculate new map level and start pos
-        let mut map_level = 0;
-        <(&mut Player, &mut Point)>::query()
-            .iter_mut(&mut self.ecs)
-            .for_each(|(player, pos)| {
-                player.map_level += 1;
-                player.wait_count += 4;
-                map_level = player.map_level;
-                pos.x = mb.player_start.x;
-                pos.y = mb.player_start.y;
-            });
-
-        // Decide on wheter we spawn staircase or teleportation crystal
-        if map_level == 3 {
-            spawn_telerportation_crystal(&mut self.ecs, mb.teleportation_crystal_start);
-        } else {
-            let exit_idx = mb.map.point2d_to_index(mb.teleportation_crystal_start);
-            mb.map.tiles[exit_idx] = TileType::Exit;
+        let n = self.inventory_cursor;
+        match ctx.key {
+            Some(VirtualKeyCode::Escape | VirtualKeyCode::I) => {
+                self.sim.resources.insert(TurnState::AwaitingInput)
+            }
+            Some(VirtualKeyCode::Up | VirtualKeyCode::W) => {
+                self.inventory_cursor = n.saturating_sub(1)
+            }
+            Some(VirtualKeyCode::Down | VirtualKeyCode::S) if n + 1 < items.len() => {
+                self.inventory_cursor = n + 1
+            }
+            Some(VirtualKeyCode::U | VirtualKeyCode::Return) if n < items.len() => {
+                match self.sim.item_area(n) {
+                    Some(radius) => self.sim.start_targeting(Aiming::Item(n, radius)),
+                    None => self.play(PlayerAction::UseItem(n)),
+                }
+            }
+            Some(VirtualKeyCode::D) if n < items.len() => self.play(PlayerAction::Drop(n)),
+            _ => {}
         }
-        spawn_level(
-            &mut self.ecs,
-            &mut rng,
-            map_level as usize,
-            &mb.monster_spawns,
-            &mb.guaranteed_monster_spawns,
+    }
+
+    fn victory(&mut self, ctx: &mut BTerm) {
+        ctx.set_active_console(2);
+        ctx.print_color_centered(2, GREEN, BLACK, "You have won!");
+        ctx.print_color_centered(
+            4,
+            WHITE,
+            BLACK,
+            "The drone could teleport home! The drone can now drone forever!!",
         );
+        ctx.print_color_centered(7, GREEN, BLACK, "Press R to help another drone");
 
-        // Finally add our ECS resources as always
-        self.resources.insert(mb.map);
-        self.resources.insert(Camera::new(mb.player_start));
-        self.resources.insert(TurnState::AwaitingInput);
-        self.resources.insert(mb.theme);
-        self.resources.insert(EventLog::new());
+        // Check for reset input
+        if let Some(VirtualKeyCode::R) = ctx.key {
+            self.reset_game_state();
+        }
     }
 }
 
@@ -268,7 +423,8 @@ impl GameState for State {
         ctx.set_active_console(0);
         // Now grab mouse position and insert as resource
         // There is a Point::from_tuple(...) but i wanted to try enum accessor pattern here
-        self.resources
+        self.sim
+            .resources
             .insert(Point::new(ctx.mouse_pos().0, ctx.mouse_pos().1));
         ctx.cls();
         ctx.set_active_console(1);
@@ -278,27 +434,108 @@ impl GameState for State {
         ctx.set_active_console(3);
         ctx.cls();
 
-        // No need to worry about dupliactes, when we insert a resource of the same type
-        // It replaces if there is an already existing resource of the same type!
-        self.resources.insert(ctx.key);
+        // Nothing to play with broken data, only show what is wrong with it
+        if !self.data_problems.is_empty() {
+            self.broken_data(ctx);
+            return;
+        }
 
-        // Execute systems, mutable borrow form ecs and resources!
-        // Depending on TurnState we execute different scheduler
-        let current_state = self.resources.get::<TurnState>().unwrap().clone();
+        // Development helpers: pick up changes to template.ron and let F5 respawn the level with them
+        if let Some(watcher) = self.template_watcher.as_mut() {
+            if let Some(templates) = watcher.poll() {
+                self.sim.reload_templates(templates);
+            }
+            if ctx.key == Some(VirtualKeyCode::F5)
+                && self.sim.turn_state() == TurnState::AwaitingInput
+            {
+                self.sim.respawn_level_entities();
+                // The key is used up, it shouldn't spend a turn waiting as well
+                ctx.key = None;
+            }
+        }
+
+        // Depending on TurnState we either show a screen or let the simulation play
+        let current_state = self.sim.turn_state();
         match current_state {
             TurnState::Menu => self.menu(ctx),
-            TurnState::AwaitingInput => self
-                .input_systems
-                .execute(&mut self.ecs, &mut self.resources),
-            TurnState::PlayerTurn => self
-                .player_systems
-                .execute(&mut self.ecs, &mut self.resources),
-            TurnState::MonsterTurn => self
-                .monster_systems
-                .execute(&mut self.ecs, &mut self.resources),
             TurnState::GameOver => self.game_over(ctx),
             TurnState::Victory => self.victory(ctx),
-            TurnState::NextLevel => self.advance_level(),
+            TurnState::LevelUp if self.playback.is_none() => self.level_up(ctx),
+            TurnState::Targeting => self.targeting(ctx),
+            TurnState::Inventory => self.inventory(ctx),
+            // Watching a replay, every key press feeds the next recorded action
+            // The replay doesn't touch the save or replay files, those belong to the player's own run
+            _ if self.playback.is_some() => {
+                let (replay, played) = self.playback.as_mut().unwrap();
+                match replay.actions.get(*played) {
+                    Some(action)
+                        if ctx.key.is_some()
+                            && matches!(
+                                current_state,
+                                TurnState::AwaitingInput | TurnState::LevelUp
+                            ) =>
+                    {
+                        *played += 1;
+                        self.sim.play_turn(*action);
+                    }
+                    _ => self.sim.tick(None),
+                }
+            }
+            // A key press plays out a whole turn, otherwise just keep the simulation ticking
+            _ => match ctx.key.map(PlayerAction::from_key) {
+                Some(_)
+                    if ctx.key == Some(VirtualKeyCode::I)
+                        && current_state == TurnState::AwaitingInput =>
+                {
+                    self.inventory_cursor = 0;
+                    self.sim.resources.insert(TurnState::Inventory);
+                }
+                // Taking aim doesn't spend the turn yet, see targeting
+                Some(_)
+                    if ctx.key == Some(VirtualKeyCode::F)
+                        && current_state == TurnState::AwaitingInput =>
+                {
+                    self.sim.start_targeting(Aiming::Fire)
+                }
+                // Items that hit an area ask where first
+                Some(PlayerAction::UseItem(n)) if current_state == TurnState::AwaitingInput => {
+                    match self.sim.item_area(n) {
+                        Some(radius) => self.sim.start_targeting(Aiming::Item(n, radius)),
+                        None => self.play(PlayerAction::UseItem(n)),
+                    }
+                }
+                Some(action) if current_state == TurnState::AwaitingInput => self.play(action),
+                _ => self.sim.tick(None),
+            },
+        }
+
+        self.last_mouse = *self.sim.resources.get::<Point>().unwrap();
+
+        // Draw the game on top of the cleared consoles while we are playing
+        if matches!(
+            self.sim.turn_state(),
+            TurnState::AwaitingInput
+                | TurnState::PlayerTurn
+                | TurnState::MonsterTurn
+                | TurnState::Targeting
+        ) {
+            self.render_systems
+                .execute(&mut self.sim.ecs, &mut self.sim.resources);
+        }
+
+        // Let whoever is watching a replay know how far along it is
+        if let Some((replay, played)) = &self.playback {
+            ctx.set_active_console(2);
+            let text = if *played < replay.actions.len() {
+                format!(
+                    "REPLAY TURN {} / {} - PRESS ANY KEY FOR THE NEXT TURN",
+                    played,
+                    replay.actions.len()
+                )
+            } else {
+                "END OF REPLAY".to_string()
+            };
+            ctx.print_color_centered(SCREEN_HEIGHT * 2 - 1, YELLOW, BLACK, text);
         }
 
         // Render draw buffer
@@ -321,5 +558,43 @@ fn main() -> BError {
         .with_simple_console_no_bg(DISPLAY_WIDTH, DISPLAY_HEIGHT, "dungeonfont.png")
         .build()?;
 
-    main_loop(context, State::new())
+    // Watch a replay with --replay <file>, asking for a specific seed with --seed <number> always starts
+    // a new run, otherwise resume the saved run if there is one
+    let mut state = if let Some(path) = arg_value("--replay") {
+        match Replay::load(&path) {
+            Ok(replay) => State::replay(replay),
+            Err(e) => {
+                let state = State::new(RunSeed::random());
+                state.report(format!("Failed loading replay {}: {}", path, e));
+                state
+            }
+        }
+    } else if let Some(seed) = arg_value("--seed").and_then(|seed| seed.parse::<u64>().ok()) {
+        State::new(RunSeed(seed))
+    } else {
+        match State::load() {
+            Ok(Some(state)) => state,
+            Ok(None) => State::new(RunSeed::random()),
+            Err(e) => {
+                let state = State::new(RunSeed::random());
+                state.report(e);
+                state
+            }
+        }
+    };
+    state.data_problems = data_problems();
+    if cfg!(debug_assertions) {
+        state.template_watcher = Some(TemplateWatcher::new());
+    }
+    main_loop(context, state)
 }
+
+// Looks for "<flag> <value>" among the command line arguments
+fn arg_value(flag: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    args.iter()
+        .position(|arg| arg == flag)
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+}
+
@@ -1,11 +1,11 @@
 use crate::prelude::*;
-
-const NUM_TILES: usize = (SCREEN_WIDTH * SCREEN_HEIGHT) as usize;
+use serde::{Deserialize, Serialize};
 
 // Clone for deep copy through .clone
 // Copy overrides the default = operator to copy over the values instead of moving
 // PartialEq lets us use == operator to compare
-#[derive(Copy, Clone, PartialEq, Debug)]
+// Eq and Hash let themes use it as a key to look up how each tile is drawn
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
 pub enum TileType {
     Wall = 0,
     Wall2 = 1,
@@ -13,19 +13,27 @@ pub enum TileType {
     Floor2 = 3, // decorative versions of floor
     Floor3 = 4,
     Exit = 5,
+    DoorClosed = 6, // Blocks sight until someone walks into it
+    DoorOpen = 7,
+    StairsUp = 8, // Leads back to the level above
 }
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Map {
+    // Every map knows its own size, it doesn't have to match the screen
+    pub width: i32,
+    pub height: i32,
     // Will use row-first encoding
     pub tiles: Vec<TileType>,
     pub revealed_tiles: Vec<bool>,
 }
 
-pub fn map_idx(x: i32, y: i32) -> usize {
-    ((y * SCREEN_WIDTH) + x) as usize
-}
-
 impl Map {
+    // Go from x,y to tile index, row-first
+    pub fn map_idx(&self, x: i32, y: i32) -> usize {
+        ((y * self.width) + x) as usize
+    }
+
     // Helper to determine if point is safe in both dimensionality and is on a valid tile
     pub fn can_enter_tile(&self, point: Point) -> bool {
         self.in_bounds(point)
@@ -34,19 +42,71 @@ impl Map {
                 TileType::Floor2,
                 TileType::Floor3,
                 TileType::Exit,
+                TileType::StairsUp,
+                TileType::DoorOpen,
             ]
-            .contains(&self.tiles[map_idx(point.x, point.y)]))
+            .contains(&self.tiles[self.map_idx(point.x, point.y)]))
+    }
+
+    // Closed doors can't be walked through, walking into one opens it instead
+    pub fn is_closed_door(&self, point: Point) -> bool {
+        self.in_bounds(point) && self.tiles[self.map_idx(point.x, point.y)] == TileType::DoorClosed
+    }
+
+    // Where someone taking the stairs ends up, right next to them so they don't take them straight back
+    pub fn arrival_point(&self, stairs: Point) -> Point {
+        [
+            Point::new(-1, 0),
+            Point::new(1, 0),
+            Point::new(0, -1),
+            Point::new(0, 1),
+        ]
+        .iter()
+        .map(|delta| stairs + *delta)
+        .find(|pt| {
+            self.can_enter_tile(*pt)
+                && ![TileType::Exit, TileType::StairsUp]
+                    .contains(&self.tiles[self.map_idx(pt.x, pt.y)])
+        })
+        .unwrap_or(stairs)
     }
 
     // Helper to determine if a point is inside of our map
     pub fn in_bounds(&self, point: Point) -> bool {
-        point.x >= 0 && point.x < SCREEN_WIDTH && point.y >= 0 && point.y < SCREEN_HEIGHT
+        point.x >= 0 && point.x < self.width && point.y >= 0 && point.y < self.height
     }
 
-    pub fn new() -> Self {
+    // The tiles a shot flies over on its way from one point to the other, it stops short of the first
+    // opaque tile and on the first tile that blocked says someone is standing on
+    pub fn line_of_fire(
+        &self,
+        from: Point,
+        to: Point,
+        blocked: impl Fn(Point) -> bool,
+    ) -> Vec<Point> {
+        let mut path = Vec::new();
+        for pt in line2d(LineAlg::Bresenham, from, to)
+            .into_iter()
+            .filter(|pt| *pt != from)
+        {
+            if !self.in_bounds(pt) || self.is_opaque(self.map_idx(pt.x, pt.y)) {
+                break;
+            }
+            path.push(pt);
+            if blocked(pt) {
+                break;
+            }
+        }
+        path
+    }
+
+    pub fn new(width: i32, height: i32) -> Self {
+        let num_tiles = (width * height) as usize;
         Self {
-            tiles: vec![TileType::Floor; NUM_TILES],
-            revealed_tiles: vec![false; NUM_TILES],
+            width,
+            height,
+            tiles: vec![TileType::Floor; num_tiles],
+            revealed_tiles: vec![false; num_tiles],
         }
     }
 
@@ -54,20 +114,19 @@ impl Map {
         if !self.in_bounds(point) {
             None
         } else {
-            Some(map_idx(point.x, point.y))
+            Some(self.map_idx(point.x, point.y))
         }
     }
 
-    fn valid_exit(&self, loc: Point, delta: Point) -> Option<usize> {
+    // Returns the index of the destination along with what it costs to get there
+    fn valid_exit(&self, loc: Point, delta: Point) -> Option<(usize, f32)> {
         // Check if destination is a walkable tile on our map
         let destination = loc + delta;
-        if self.in_bounds(destination) {
-            if self.can_enter_tile(destination) {
-                let idx = self.point2d_to_index(destination);
-                Some(idx)
-            } else {
-                None
-            }
+        if self.can_enter_tile(destination) {
+            Some((self.point2d_to_index(destination), 1.0))
+        } else if self.is_closed_door(destination) {
+            // Opening the door takes a turn of its own
+            Some((self.point2d_to_index(destination), 2.0))
         } else {
             None
         }
@@ -90,17 +149,17 @@ impl BaseMap for Map {
 
         // Returns a list of available exits from a point idx with the second argument being weight
         // Check all 4 directions, enemies can only move orthogonal
-        if let Some(idx) = self.valid_exit(location, Point::new(-1, 0)) {
-            exits.push((idx, 1.0));
+        if let Some(exit) = self.valid_exit(location, Point::new(-1, 0)) {
+            exits.push(exit);
         }
-        if let Some(idx) = self.valid_exit(location, Point::new(1, 0)) {
-            exits.push((idx, 1.0));
+        if let Some(exit) = self.valid_exit(location, Point::new(1, 0)) {
+            exits.push(exit);
         }
-        if let Some(idx) = self.valid_exit(location, Point::new(0, -1)) {
-            exits.push((idx, 1.0));
+        if let Some(exit) = self.valid_exit(location, Point::new(0, -1)) {
+            exits.push(exit);
         }
-        if let Some(idx) = self.valid_exit(location, Point::new(0, 1)) {
-            exits.push((idx, 1.0));
+        if let Some(exit) = self.valid_exit(location, Point::new(0, 1)) {
+            exits.push(exit);
         }
 
         exits
@@ -112,7 +171,13 @@ impl BaseMap for Map {
 
     // Is the tile a wall? (opaque)
     fn is_opaque(&self, idx: usize) -> bool {
-        ![TileType::Floor, TileType::Floor2, TileType::Floor3].contains(&self.tiles[idx as usize])
+        ![
+            TileType::Floor,
+            TileType::Floor2,
+            TileType::Floor3,
+            TileType::DoorOpen,
+        ]
+        .contains(&self.tiles[idx])
     }
 }
 
@@ -134,7 +199,7 @@ impl Algorithm2D for Map {
     }
 
     fn dimensions(&self) -> Point {
-        Point::new(SCREEN_WIDTH, SCREEN_HEIGHT)
+        Point::new(self.width, self.height)
     }
 
     fn in_bounds(&self, point: Point) -> bool {
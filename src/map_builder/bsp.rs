@@ -0,0 +1,155 @@
+use super::MapArchitect;
+use crate::prelude::*;
+
+// Smallest area we are still allowed to split, anything smaller than twice this becomes a leaf
+const MIN_LEAF_SIZE: i32 = 8;
+
+// Binary space partition: keep cutting the map in two, put one room in every leaf and
+// connect the two halves of every cut with a corridor on the way back up the tree
+pub struct BspArchitect {
+    pub rooms: Vec<Rect>,
+}
+
+impl BspArchitect {
+    // Splits the area (x2/y2 exclusive) until it is too small, returns the center of a room inside it
+    // so the caller can hook a corridor onto this part of the tree
+    fn partition(&mut self, rng: &mut RandomNumberGenerator, map: &mut Map, area: Rect) -> Point {
+        let can_split_x = area.width() >= MIN_LEAF_SIZE * 2;
+        let can_split_y = area.height() >= MIN_LEAF_SIZE * 2;
+
+        // Prefer cutting across the longest side, that keeps the leaves roughly square
+        let split_x = match (can_split_x, can_split_y) {
+            (false, false) => return self.place_room(rng, map, area),
+            (true, false) => true,
+            (false, true) => false,
+            (true, true) => {
+                if area.width() > area.height() + area.height() / 4 {
+                    true
+                } else if area.height() > area.width() + area.width() / 4 {
+                    false
+                } else {
+                    rng.range(0, 2) == 1
+                }
+            }
+        };
+
+        let (first, second) = if split_x {
+            let cut = area.x1 + rng.range(MIN_LEAF_SIZE, area.width() - MIN_LEAF_SIZE + 1);
+            (
+                Rect::with_exact(area.x1, area.y1, cut, area.y2),
+                Rect::with_exact(cut, area.y1, area.x2, area.y2),
+            )
+        } else {
+            let cut = area.y1 + rng.range(MIN_LEAF_SIZE, area.height() - MIN_LEAF_SIZE + 1);
+            (
+                Rect::with_exact(area.x1, area.y1, area.x2, cut),
+                Rect::with_exact(area.x1, cut, area.x2, area.y2),
+            )
+        };
+
+        let a = self.partition(rng, map, first);
+        let b = self.partition(rng, map, second);
+        self.carve_corridor(rng, map, a, b);
+
+        // Either side will do as the connection point for the parent
+        if rng.range(0, 2) == 1 {
+            a
+        } else {
+            b
+        }
+    }
+
+    // Places a randomly sized room in the leaf, leaving at least one tile of wall around it
+    fn place_room(&mut self, rng: &mut RandomNumberGenerator, map: &mut Map, leaf: Rect) -> Point {
+        let w = Self::room_size(rng, leaf.width());
+        let h = Self::room_size(rng, leaf.height());
+        // Rooms include their x2/y2 edge, same as the ones RoomArchitect digs
+        let room = Rect::with_size(
+            leaf.x1 + Self::room_offset(rng, leaf.width(), w),
+            leaf.y1 + Self::room_offset(rng, leaf.height(), h),
+            w - 1,
+            h - 1,
+        );
+
+        room.for_each(|p| {
+            if let Some(idx) = map.try_idx(p) {
+                map.tiles[idx] = TileType::Floor;
+            }
+        });
+
+        self.rooms.push(room);
+        room.center()
+    }
+
+    // A room wants at least 3 tiles and a wall on either side, on a small map a leaf can be too narrow
+    // for that and the room fills it instead
+    fn room_size(rng: &mut RandomNumberGenerator, leaf_size: i32) -> i32 {
+        if leaf_size >= 5 {
+            rng.range(3, leaf_size - 1)
+        } else {
+            leaf_size.max(1)
+        }
+    }
+
+    // Where the room starts in the leaf, past the wall unless it fills the leaf
+    fn room_offset(rng: &mut RandomNumberGenerator, leaf_size: i32, room_size: i32) -> i32 {
+        if room_size >= leaf_size {
+            0
+        } else {
+            rng.range(1, leaf_size - room_size)
+        }
+    }
+
+    // L shaped corridor between two points, randomly going horizontal or vertical first
+    fn carve_corridor(&mut self, rng: &mut RandomNumberGenerator, map: &mut Map, a: Point, b: Point) {
+        let corner = if rng.range(0, 2) == 1 {
+            Point::new(b.x, a.y)
+        } else {
+            Point::new(a.x, b.y)
+        };
+
+        for (from, to) in [(a, corner), (corner, b)] {
+            for x in from.x.min(to.x)..=from.x.max(to.x) {
+                for y in from.y.min(to.y)..=from.y.max(to.y) {
+                    if let Some(idx) = map.try_idx(Point::new(x, y)) {
+                        map.tiles[idx] = TileType::Floor;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl MapArchitect for BspArchitect {
+    fn build(&mut self, rng: &mut RandomNumberGenerator, width: i32, height: i32) -> MapBuilder {
+        let mut mb = MapBuilder::from_map(Map::new(width, height));
+
+        mb.fill(TileType::Wall);
+        // Keep the outermost ring of tiles as wall
+        self.partition(rng, &mut mb.map, Rect::with_exact(1, 1, width - 1, height - 1));
+        mb.player_start = self.rooms[0].center();
+        mb.rooms = self.rooms.clone();
+
+        mb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_on_small_maps() {
+        for (width, height) in [(20, 15), (12, 10), (6, 6), (3, 3)] {
+            for seed in 0..50 {
+                let mut rng = RandomNumberGenerator::seeded(seed);
+                let mb = BspArchitect { rooms: Vec::new() }.build(&mut rng, width, height);
+                assert!(mb.map.can_enter_tile(mb.player_start));
+                assert!(mb
+                    .rooms
+                    .iter()
+                    .all(|room| room.x2 < width && room.y2 < height));
+            }
+        }
+    }
+}
@@ -1,70 +1,118 @@
 use crate::prelude::*;
-use themes::*;
-
-use self::prefab::PREFAB_LIST;
+use pipeline::*;
+pub use prefab::VaultLibrary;
+pub use themes::ThemeLibrary;
+pub use wfc::sample_by_name;
 
 mod automata;
+mod bsp;
 mod drunkard;
 mod empty;
+mod pipeline;
 mod prefab;
 mod rooms;
 mod themes;
+mod wfc;
 
 trait MapArchitect {
-    fn new(&mut self, rng: &mut RandomNumberGenerator) -> MapBuilder;
+    // Builds a map of the given size
+    fn build(&mut self, rng: &mut RandomNumberGenerator, width: i32, height: i32) -> MapBuilder;
 }
 
 // A large part of how Rust enforced safe concurrency between threads is through these new types 'Sync' and 'Send'
 // Sync lets us safe access this object from multipile threads
 // Send lets us safe share this object between threads
 pub trait MapTheme: Sync + Send {
-    fn tile_to_render(&self, tile_type: TileType) -> FontCharType;
+    // Glyph and colours for a tile, the index lets a theme vary its glyphs across the map
+    fn tile_to_render(&self, tile_type: TileType, idx: usize) -> (FontCharType, ColorPair);
+    // Identifier we can write to a save file and turn back into a theme with ThemeLibrary::theme
+    fn name(&self) -> &str;
 }
 
 pub struct MapBuilder {
     pub map: Map,
     pub player_start: Point,
     pub teleportation_crystal_start: Point,
+    // Only filled in by the architects that think in rooms, used to spawn a monster per room
+    pub rooms: Vec<Rect>,
     pub monster_spawns: Vec<Point>,
     pub guaranteed_monster_spawns: Vec<Point>,
+    // Specific templates (by name) placed at exact spots, so far only by the vaults
+    pub template_spawns: Vec<(Point, String)>,
     pub theme: Box<dyn MapTheme>,
 }
 
 impl MapBuilder {
-    pub fn new(rng: &mut RandomNumberGenerator, map_level: u32) -> Self {
-        let theme_name: &str;
-        // Build map and select appropiate theme
-        let mut architect: Box<dyn MapArchitect> = match map_level {
-            0 | 1 => {
-                theme_name = "forest";
-                Box::new(automata::CellularAutomataArchitect {})
-            }
-            2 => {
-                theme_name = "cave";
-                Box::new(drunkard::DrunkardsWalkArchitect {})
-            }
-            _ => {
-                // theme_name = "dungeon";
-                theme_name = "dungeon";
-                Box::new(rooms::RoomArchitect { rooms: Vec::new() })
-                // Box::new(drunkard::DrunkardsWalkArchitect {})
-            }
+    // Follows the campaign's recipe for the level: an architect lays out the map, the steps after it fill it in
+    pub fn new(
+        rng: &mut RandomNumberGenerator,
+        map_level: u32,
+        level: &CampaignLevel,
+        vaults: &VaultLibrary,
+        themes: &ThemeLibrary,
+        width: i32,
+        height: i32,
+    ) -> Self {
+        // The campaign is checked for levels without architects when it loads, an empty room will do for those
+        let architect: Box<dyn MapArchitect> = match level
+            .architects
+            .get(rng.range(0, level.architects.len().max(1)))
+            .unwrap_or(&ArchitectKind::Empty)
+        {
+            ArchitectKind::Empty => Box::new(empty::EmptyArchitect {}),
+            ArchitectKind::Rooms => Box::new(rooms::RoomArchitect { rooms: Vec::new() }),
+            ArchitectKind::Bsp => Box::new(bsp::BspArchitect { rooms: Vec::new() }),
+            ArchitectKind::CellularAutomata => Box::new(automata::CellularAutomataArchitect {}),
+            ArchitectKind::DrunkardsWalk => Box::new(drunkard::DrunkardsWalkArchitect {}),
+            // Unknown samples are listed on the data problems screen, the caves will do until then
+            ArchitectKind::WaveFunctionCollapse(sample) => Box::new(wfc::WfcArchitect {
+                sample: sample_by_name(sample).unwrap_or(wfc::CAVE_SAMPLE),
+            }),
         };
 
-        let mut mb = architect.new(rng);
-        // Randomly select a prefab variant
-        let prefab = PREFAB_LIST[rng.range(0, PREFAB_LIST.len())];
-        prefab::apply_prefab(&mut mb, rng, prefab);
-
-        // Decide on theme
-        match theme_name {
-            "dungeon" => mb.theme = DungeonTheme::new(),
-            "forest" => mb.theme = ForestTheme::new(),
-            "cave" => mb.theme = CaveTheme::new(),
-            _ => mb.theme = DungeonTheme::new(),
+        let mut pipeline = MapPipeline::new(architect, themes.theme(&level.theme));
+        for step in level.steps.iter() {
+            pipeline = match step {
+                BuildStep::CullUnreachable => pipeline.step(CullUnreachable {}),
+                BuildStep::RoomDoors => pipeline.step(RoomDoors {}),
+                BuildStep::RoomSpawns => pipeline.step(RoomSpawns {
+                    budget: level.spawn_budget,
+                }),
+                BuildStep::AreaSpawns => pipeline.step(AreaSpawns {
+                    budget: level.spawn_budget,
+                }),
+                BuildStep::PlaceExitFurthest => pipeline.step(PlaceExitFurthest {}),
+                BuildStep::DungeonDecorations => pipeline.step(DungeonDecorations {}),
+                BuildStep::ForestDecorations => pipeline.step(ForestDecorations {}),
+                BuildStep::CaveDecorations => pipeline.step(CaveDecorations {}),
+            };
         }
 
-        mb
+        // Every level gets a shot at one of the vaults from its pool that may show up down here
+        let vaults = vaults
+            .vaults
+            .iter()
+            .filter(|vault| level.prefabs.contains(&vault.name) && vault.fits(map_level, level))
+            .cloned()
+            .collect();
+        pipeline
+            .step(RandomPrefab { vaults })
+            .build(rng, width, height)
+    }
+
+    // An empty builder around the given map, architects start from here and steps can be tried out on it
+    pub fn from_map(map: Map) -> Self {
+        Self {
+            map,
+            player_start: Point::zero(),
+            teleportation_crystal_start: Point::zero(),
+            rooms: Vec::new(),
+            monster_spawns: Vec::new(),
+            guaranteed_monster_spawns: Vec::new(),
+            template_spawns: Vec::new(),
+            // Stand-in until the pipeline hands over the level's theme
+            theme: ThemeLibrary::default().theme("dungeon"),
+        }
     }
 
     fn fill(&mut self, tile: TileType) {
@@ -74,9 +122,9 @@ impl MapBuilder {
 
     fn find_most_distant(&self) -> Point {
         let djikstra_map = DijkstraMap::new(
-            SCREEN_WIDTH,
-            SCREEN_HEIGHT,
-            &vec![self.map.point2d_to_index(self.player_start)],
+            self.map.width,
+            self.map.height,
+            &[self.map.point2d_to_index(self.player_start)],
             &self.map,
             1024.0,
         );
@@ -96,11 +144,12 @@ impl MapBuilder {
     }
 }
 
-// Returns a vector of spawn points atleast 10 points away from the player
+// Returns a vector of up to budget spawn points atleast 5 points away from the player
 pub fn spawn_monsters(
     mb: &MapBuilder,
     start: &Point,
     rng: &mut RandomNumberGenerator,
+    budget: usize,
 ) -> Vec<Point> {
     let mut available_spawns: Vec<Point> = mb
         .map
@@ -114,14 +163,13 @@ pub fn spawn_monsters(
         .map(|(i, _)| mb.map.index_to_point2d(i))
         .collect();
 
-    const NUM_MONSTERS: usize = 50;
     let mut spawns = Vec::new();
-    for _ in 0..NUM_MONSTERS {
+    for _ in 0..budget {
         if available_spawns.is_empty() {
             break;
         }
         let target_index = rng.random_slice_index(&available_spawns).unwrap();
-        spawns.push(available_spawns[target_index].clone());
+        spawns.push(available_spawns[target_index]);
         available_spawns.remove(target_index);
     }
 
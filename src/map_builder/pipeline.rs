@@ -0,0 +1,395 @@
+use super::prefab::{apply_prefab, Vault};
+use super::MapArchitect;
+use crate::prelude::*;
+
+// A step that runs after the architect has laid out the map, each one takes care of a single thing
+// so level recipes can be put together from them in MapBuilder::new
+pub trait MetaBuilder {
+    fn apply(&mut self, mb: &mut MapBuilder, rng: &mut RandomNumberGenerator);
+}
+
+// An architect followed by any number of steps, run in the order they were added
+pub struct MapPipeline {
+    architect: Box<dyn MapArchitect>,
+    steps: Vec<Box<dyn MetaBuilder>>,
+    theme: Box<dyn MapTheme>,
+}
+
+impl MapPipeline {
+    pub fn new(architect: Box<dyn MapArchitect>, theme: Box<dyn MapTheme>) -> Self {
+        Self {
+            architect,
+            steps: Vec::new(),
+            theme,
+        }
+    }
+
+    pub fn step(mut self, step: impl MetaBuilder + 'static) -> Self {
+        self.steps.push(Box::new(step));
+        self
+    }
+
+    pub fn build(mut self, rng: &mut RandomNumberGenerator, width: i32, height: i32) -> MapBuilder {
+        let mut mb = self.architect.build(rng, width, height);
+        for step in self.steps.iter_mut() {
+            step.apply(&mut mb, rng);
+        }
+        mb.theme = self.theme;
+        mb
+    }
+}
+
+/* META BUILDER IMPLEMENTATIONS STARTS HERE */
+
+// Stamps one of the vaults somewhere on the map if it fits, the heavier vaults get picked more often
+pub struct RandomPrefab {
+    pub vaults: Vec<Vault>,
+}
+
+impl MetaBuilder for RandomPrefab {
+    fn apply(&mut self, mb: &mut MapBuilder, rng: &mut RandomNumberGenerator) {
+        let total_weight: i32 = self.vaults.iter().map(|vault| vault.weight).sum();
+        if total_weight == 0 {
+            return;
+        }
+        let mut roll = rng.range(0, total_weight);
+        for vault in self.vaults.iter() {
+            if roll < vault.weight {
+                apply_prefab(mb, rng, vault);
+                return;
+            }
+            roll -= vault.weight;
+        }
+    }
+}
+
+// Walls off every part of the map the player can't walk to from the start
+pub struct CullUnreachable {}
+
+impl MetaBuilder for CullUnreachable {
+    fn apply(&mut self, mb: &mut MapBuilder, _rng: &mut RandomNumberGenerator) {
+        let dijkstra_map = DijkstraMap::new(
+            mb.map.width,
+            mb.map.height,
+            &[mb.map.point2d_to_index(mb.player_start)],
+            &mb.map,
+            1024.0,
+        );
+        dijkstra_map
+            .map
+            .iter()
+            .enumerate()
+            .filter(|(_, distance)| **distance == f32::MAX)
+            .for_each(|(idx, _)| mb.map.tiles[idx] = TileType::Wall);
+        // Spawns that ended up inside a wall are no use to anyone
+        let map = &mb.map;
+        mb.monster_spawns.retain(|pt| map.can_enter_tile(*pt));
+    }
+}
+
+// Closed doors wherever a corridor runs into one of the rooms
+pub struct RoomDoors {}
+
+impl RoomDoors {
+    fn is_wall(map: &Map, x: i32, y: i32) -> bool {
+        match map.try_idx(Point::new(x, y)) {
+            Some(idx) => [TileType::Wall, TileType::Wall2].contains(&map.tiles[idx]),
+            None => true,
+        }
+    }
+
+    // A door only fits in a one tile wide gap, with wall on both sides of it
+    fn try_door(map: &mut Map, x: i32, y: i32, horizontal: bool) {
+        let (a, b) = if horizontal {
+            (Self::is_wall(map, x - 1, y), Self::is_wall(map, x + 1, y))
+        } else {
+            (Self::is_wall(map, x, y - 1), Self::is_wall(map, x, y + 1))
+        };
+        if let Some(idx) = map.try_idx(Point::new(x, y)) {
+            if map.tiles[idx] == TileType::Floor && a && b {
+                map.tiles[idx] = TileType::DoorClosed;
+            }
+        }
+    }
+}
+
+impl MetaBuilder for RoomDoors {
+    fn apply(&mut self, mb: &mut MapBuilder, _rng: &mut RandomNumberGenerator) {
+        // Walk the ring of tiles just outside every room
+        for room in mb.rooms.iter() {
+            for x in room.x1..=room.x2 {
+                Self::try_door(&mut mb.map, x, room.y1 - 1, true);
+                Self::try_door(&mut mb.map, x, room.y2 + 1, true);
+            }
+            for y in room.y1..=room.y2 {
+                Self::try_door(&mut mb.map, room.x1 - 1, y, false);
+                Self::try_door(&mut mb.map, room.x2 + 1, y, false);
+            }
+        }
+    }
+}
+
+// One monster in the middle of every room but the one we start in, as long as the budget lasts
+pub struct RoomSpawns {
+    pub budget: usize,
+}
+
+impl MetaBuilder for RoomSpawns {
+    fn apply(&mut self, mb: &mut MapBuilder, _rng: &mut RandomNumberGenerator) {
+        for room in mb.rooms.iter().skip(1).take(self.budget) {
+            mb.monster_spawns.push(room.center());
+        }
+    }
+}
+
+// Monsters scattered all over the floor, keeping some distance to the player
+pub struct AreaSpawns {
+    pub budget: usize,
+}
+
+impl MetaBuilder for AreaSpawns {
+    fn apply(&mut self, mb: &mut MapBuilder, rng: &mut RandomNumberGenerator) {
+        let start = mb.player_start;
+        mb.monster_spawns = spawn_monsters(mb, &start, rng, self.budget);
+    }
+}
+
+// The exit (or the crystal) goes as far away from the player as we can walk
+pub struct PlaceExitFurthest {}
+
+impl MetaBuilder for PlaceExitFurthest {
+    fn apply(&mut self, mb: &mut MapBuilder, _rng: &mut RandomNumberGenerator) {
+        mb.teleportation_crystal_start = mb.find_most_distant();
+    }
+}
+
+// Cracked walls and tiled floors for the dungeon
+pub struct DungeonDecorations {}
+
+impl MetaBuilder for DungeonDecorations {
+    fn apply(&mut self, mb: &mut MapBuilder, rng: &mut RandomNumberGenerator) {
+        // Turn some floor tiles into decorated, complete 50/50 chance
+        mb.map
+            .tiles
+            .iter_mut()
+            .filter(|t| **t == TileType::Floor && rng.range(0, 11) > 5)
+            .for_each(|t| *t = TileType::Floor2);
+
+        // Turn some wall tiles into decorated, favor-non decorated wall tiles
+        mb.map
+            .tiles
+            .iter_mut()
+            .filter(|t| **t == TileType::Wall && rng.range(0, 11) > 6)
+            .for_each(|t| *t = TileType::Wall2);
+    }
+}
+
+// Patches of grass and the odd mushroom for the forest
+// They grow through the trees as well, so list it before CullUnreachable in the campaign
+pub struct ForestDecorations {}
+
+impl MetaBuilder for ForestDecorations {
+    fn apply(&mut self, mb: &mut MapBuilder, rng: &mut RandomNumberGenerator) {
+        let map = &mut mb.map;
+        let dirs: [Point; 4] = [
+            Point::new(-1, 0),
+            Point::new(1, 0),
+            Point::new(0, -1),
+            Point::new(0, 1),
+        ];
+        // First sprinkle completely random points on the map
+        map.tiles.iter_mut().for_each(|t| {
+            if rng.range(0, 15) < 1 {
+                *t = TileType::Floor2;
+            }
+        });
+
+        let mut points_to_grow_grass: Vec<Point> = Vec::new();
+
+        for y in 1..map.height - 1 {
+            for x in 1..map.width - 1 {
+                let idx = map.point2d_to_index(Point::new(x, y));
+                // Is it a grass tile?
+                if map.tiles[idx] == TileType::Floor2 {
+                    dirs.iter().for_each(|dir| {
+                        let pos = Point::new(x, y) + *dir;
+                        // Is neighbour in bound + a floor? turn it into grass
+                        if map.in_bounds(pos) {
+                            let neighbour_idx = map.point2d_to_index(pos);
+                            if map.tiles[neighbour_idx] == TileType::Floor {
+                                points_to_grow_grass.push(pos);
+                            }
+                        }
+                    });
+                }
+            }
+        }
+
+        // Actually grow the grass
+        points_to_grow_grass.iter().for_each(|pos| {
+            let idx = map.point2d_to_index(*pos);
+            map.tiles[idx] = TileType::Floor2;
+        });
+
+        // Sprinkle some random mushrooms on the map finally
+        // First sprinkle completely random points on the map
+        map.tiles.iter_mut().for_each(|t| {
+            if rng.range(0, 15) < 1 {
+                *t = TileType::Floor3;
+            }
+        });
+    }
+}
+
+// Worn paths from the monsters to the player and some cracked walls for the caves
+pub struct CaveDecorations {}
+
+impl MetaBuilder for CaveDecorations {
+    fn apply(&mut self, mb: &mut MapBuilder, rng: &mut RandomNumberGenerator) {
+        let map = &mut mb.map;
+        let start = map.point2d_to_index(mb.player_start);
+        let djikstra_map = DijkstraMap::new(map.width, map.height, &[start], &*map, 2048.0);
+        // Carve paths in the map from each monster to start
+        mb.monster_spawns.iter().for_each(|target| {
+            let mut current = map.point2d_to_index(*target);
+            while current != start {
+                match DijkstraMap::find_lowest_exit(&djikstra_map, current, &*map) {
+                    Some(destination) => {
+                        // Check distance
+                        let pos1 = map.index_to_point2d(current);
+                        let pos2 = map.index_to_point2d(start);
+                        if DistanceAlg::Pythagoras.distance2d(pos1, pos2) < 1.2 {
+                            break;
+                        }
+                        map.tiles[destination] = TileType::Floor2;
+                        current = destination;
+                    }
+                    // Nowhere left to go, can't reach the player from here
+                    None => break,
+                }
+            }
+        });
+
+        // Turn some tiles into decorated
+        map.tiles
+            .iter_mut()
+            .filter(|t| **t == TileType::Wall && rng.range(0, 10) > 5)
+            .for_each(|t| *t = TileType::Wall2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A builder around a map drawn with '#' for walls, '.' for floor and '@' where the player starts
+    fn builder(drawing: &str) -> MapBuilder {
+        let rows: Vec<&str> = drawing.lines().map(str::trim).collect();
+        let mut mb = MapBuilder::from_map(Map::new(rows[0].len() as i32, rows.len() as i32));
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let idx = mb.map.map_idx(x as i32, y as i32);
+                mb.map.tiles[idx] = match c {
+                    '#' => TileType::Wall,
+                    _ => TileType::Floor,
+                };
+                if c == '@' {
+                    mb.player_start = Point::new(x, y);
+                }
+            }
+        }
+        mb
+    }
+
+    fn tile(mb: &MapBuilder, x: i32, y: i32) -> TileType {
+        mb.map.tiles[mb.map.map_idx(x, y)]
+    }
+
+    #[test]
+    fn cull_unreachable_walls_off_what_the_player_cant_get_to() {
+        let mut mb = builder(
+            "#########
+             #@..#...#
+             #...#...#
+             #########",
+        );
+        mb.monster_spawns = vec![Point::new(2, 2), Point::new(6, 2)];
+        CullUnreachable {}.apply(&mut mb, &mut RandomNumberGenerator::seeded(1));
+
+        assert_eq!(tile(&mb, 3, 2), TileType::Floor);
+        assert!(
+            (5..8).all(|x| tile(&mb, x, 1) == TileType::Wall && tile(&mb, x, 2) == TileType::Wall)
+        );
+        assert_eq!(mb.monster_spawns, vec![Point::new(2, 2)]);
+    }
+
+    #[test]
+    fn room_doors_close_off_a_corridor_into_a_room() {
+        let mut mb = builder(
+            "#########
+             #@..#####
+             #...#####
+             #........
+             #...#####
+             #########",
+        );
+        mb.rooms = vec![Rect::with_size(1, 1, 3, 4)];
+        RoomDoors {}.apply(&mut mb, &mut RandomNumberGenerator::seeded(1));
+
+        assert_eq!(tile(&mb, 5, 3), TileType::DoorClosed);
+        let doors = mb
+            .map
+            .tiles
+            .iter()
+            .filter(|t| **t == TileType::DoorClosed)
+            .count();
+        assert_eq!(doors, 1);
+    }
+
+    #[test]
+    fn place_exit_furthest_picks_the_furthest_tile_the_player_can_walk_to() {
+        let mut mb = builder(
+            "##########
+             #@.....#.#
+             ##########",
+        );
+        PlaceExitFurthest {}.apply(&mut mb, &mut RandomNumberGenerator::seeded(1));
+
+        assert_eq!(mb.teleportation_crystal_start, Point::new(6, 1));
+    }
+
+    #[test]
+    fn forest_levels_can_be_walked_everywhere() {
+        let campaign = Campaign::built_in();
+        let forests = campaign
+            .levels
+            .iter()
+            .enumerate()
+            .filter(|(_, level)| level.steps.contains(&BuildStep::ForestDecorations));
+        for (map_level, level) in forests {
+            for seed in 0..10 {
+                let mb = MapBuilder::new(
+                    &mut RandomNumberGenerator::seeded(seed),
+                    map_level as u32,
+                    level,
+                    &VaultLibrary::default(),
+                    &ThemeLibrary::default(),
+                    MAP_WIDTH,
+                    MAP_HEIGHT,
+                );
+                let dijkstra_map = DijkstraMap::new(
+                    mb.map.width,
+                    mb.map.height,
+                    &[mb.map.point2d_to_index(mb.player_start)],
+                    &mb.map,
+                    1024.0,
+                );
+                let stranded = (0..mb.map.tiles.len())
+                    .filter(|idx| mb.map.can_enter_tile(mb.map.index_to_point2d(*idx)))
+                    .filter(|idx| dijkstra_map.map[*idx] == f32::MAX)
+                    .count();
+                assert_eq!(stranded, 0, "level {} seed {}", map_level, seed);
+            }
+        }
+    }
+}
@@ -1,45 +1,174 @@
 use crate::prelude::*;
+use ron::de::from_str;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
 
-#[rustfmt::skip]
-pub const FORTRESS: (&str, i32, i32) = ("
-------------
----######---
----#-XX-#---
----#-XX-#---
--###----###-
-------------
--###----###-
----#-XX-#---
----#-XX-#---
----######---
-------------
-", 12, 11
-);
-
-#[rustfmt::skip]
-pub const MONSTER_FORTRESS: (&str, i32, i32) = ("
-######
-M----M
-#----#
-#X--X#
-#-XX-#
-######
-", 6, 6);
-
-pub const PREFAB_LIST: [(&str, i32, i32); 4] = [FORTRESS, FORTRESS, FORTRESS, MONSTER_FORTRESS];
-
-pub fn apply_prefab(
-    mb: &mut MapBuilder,
+// Every glyph a vault can be drawn with on top of its own legend, anything else is rejected when the vault is loaded
+// '>' is the level exit and '*' the teleportation crystal, both take over from wherever the level had put them
+const VAULT_GLYPHS: [char; 7] = ['-', '#', '+', 'M', 'X', '>', '*'];
+
+// This struct is reflected in every .ron file of the prefabs directory, the file name is the vault's name
+#[derive(Clone, Deserialize, Debug)]
+pub struct Vault {
+    #[serde(skip)]
+    pub name: String,
+    // How likely the vault is picked compared to the others that fit the level
+    pub weight: i32,
+    // Depths (map levels) and themes the vault may show up in, leaving one out means anywhere
+    #[serde(default)]
+    pub depths: HashSet<u32>,
+    #[serde(default)]
+    pub themes: HashSet<String>,
+    pub rows: Vec<String>,
+    // Extra glyphs standing for a template from template.ron, e.g. 'O': "Ogre"
+    #[serde(default)]
+    pub legend: HashMap<char, String>,
+}
+
+impl Vault {
+    pub fn fits(&self, map_level: u32, level: &CampaignLevel) -> bool {
+        (self.depths.is_empty() || self.depths.contains(&map_level))
+            && (self.themes.is_empty() || self.themes.contains(&level.theme))
+            // An exit makes no sense next to the crystal and the crystal only belongs on its own level
+            && (!self.contains('>') || !level.teleportation_crystal)
+            && (!self.contains('*') || level.teleportation_crystal)
+    }
+
+    fn contains(&self, glyph: char) -> bool {
+        self.rows.iter().any(|row| row.contains(glyph))
+    }
+
+    // Catch broken vaults when they are loaded instead of half way through building a level
+    // Returns everything that is wrong with it so it can all be fixed in one go
+    fn validate(&self, templates: &Templates) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.weight < 1 {
+            problems.push(format!(
+                "vault {} needs a weight of at least 1, got {}",
+                self.name, self.weight
+            ));
+        }
+        for (glyph, name) in self.legend.iter() {
+            if VAULT_GLYPHS.contains(glyph) {
+                problems.push(format!(
+                    "vault {}: '{}' is a built in glyph and can't be used in the legend",
+                    self.name, glyph
+                ));
+            }
+            if !templates.has(name) {
+                problems.push(format!(
+                    "vault {}: '{}' stands for {} but template.ron has no such template",
+                    self.name, glyph, name
+                ));
+            }
+        }
+        let width = match self.rows.first() {
+            Some(row) if !row.is_empty() => row.chars().count(),
+            _ => {
+                problems.push(format!("vault {} has no tiles", self.name));
+                return problems;
+            }
+        };
+        for (y, row) in self.rows.iter().enumerate() {
+            if row.chars().count() != width {
+                problems.push(format!(
+                    "vault {}: row {} is {} tiles wide, the first row is {}",
+                    self.name,
+                    y + 1,
+                    row.chars().count(),
+                    width
+                ));
+            }
+            row.chars()
+                .enumerate()
+                .filter(|(_, c)| !VAULT_GLYPHS.contains(c) && !self.legend.contains_key(c))
+                .for_each(|(x, c)| {
+                    problems.push(format!(
+                        "vault {}: unknown glyph '{}' at row {}, column {}",
+                        self.name,
+                        c,
+                        y + 1,
+                        x + 1
+                    ))
+                });
+        }
+        problems
+    }
+}
+
+// Every vault the game knows about, parsed once and kept as a resource
+#[derive(Clone, Default, Debug)]
+pub struct VaultLibrary {
+    pub vaults: Vec<Vault>,
+}
+
+impl VaultLibrary {
+    // Reads the vaults we were built with along with any modded ones, see data_file
+    // Err lists the problems with every vault, not just the first broken one
+    pub fn load(templates: &Templates) -> Result<Self, Vec<String>> {
+        let mut vaults = Vec::new();
+        let mut problems = Vec::new();
+        for (name, contents) in vault_files() {
+            let parsed = contents.and_then(|contents| {
+                from_str::<Vault>(&contents)
+                    .map_err(|e| format!("failed parsing vault {}: {}", name, e))
+            });
+            match parsed {
+                Ok(mut vault) => {
+                    vault.name = name;
+                    problems.extend(vault.validate(templates));
+                    vaults.push(vault);
+                }
+                Err(problem) => problems.push(problem),
+            }
+        }
+        if problems.is_empty() {
+            Ok(Self { vaults })
+        } else {
+            Err(problems)
+        }
+    }
+}
+
+// Turns the vault a random number of quarter turns and maybe mirrors it
+// Returns the tiles row by row along with the new width and height
+fn random_orientation(
+    vault: &Vault,
     rng: &mut RandomNumberGenerator,
-    prefab: (&str, i32, i32),
-) {
+) -> (Vec<Vec<char>>, i32, i32) {
+    let mut tiles: Vec<Vec<char>> = vault.rows.iter().map(|row| row.chars().collect()).collect();
+
+    for _ in 0..rng.range(0, 4) {
+        // Rotate clockwise: the first column read bottom up becomes the first row
+        let height = tiles.len();
+        let width = tiles[0].len();
+        tiles = (0..width)
+            .map(|x| (0..height).rev().map(|y| tiles[y][x]).collect())
+            .collect();
+    }
+    if rng.range(0, 2) == 1 {
+        tiles.iter_mut().for_each(|row| row.reverse());
+    }
+
+    let width = tiles[0].len() as i32;
+    let height = tiles.len() as i32;
+    (tiles, width, height)
+}
+
+pub fn apply_prefab(mb: &mut MapBuilder, rng: &mut RandomNumberGenerator, vault: &Vault) {
+    let (tiles, width, height) = random_orientation(vault, rng);
     let mut placement: Option<Point> = None;
 
+    // A prefab as big as the map or bigger can never fit
+    if width >= mb.map.width || height >= mb.map.height {
+        return;
+    }
+
     // Dijkstra map with our player start as start
     let dijkstra_map = DijkstraMap::new(
-        SCREEN_WIDTH,
-        SCREEN_HEIGHT,
-        &vec![mb.map.point2d_to_index(mb.player_start)],
+        mb.map.width,
+        mb.map.height,
+        &[mb.map.point2d_to_index(mb.player_start)],
         &mb.map,
         1024.0,
     );
@@ -49,10 +178,10 @@ pub fn apply_prefab(
     while placement.is_none() && attempts < 10 {
         // Size of the prefab
         let dimensions = Rect::with_size(
-            rng.range(0, SCREEN_WIDTH - prefab.1),
-            rng.range(0, SCREEN_HEIGHT - prefab.2),
-            prefab.1,
-            prefab.2,
+            rng.range(0, mb.map.width - width),
+            rng.range(0, mb.map.height - height),
+            width,
+            height,
         );
 
         let mut can_place = false;
@@ -65,6 +194,18 @@ pub fn apply_prefab(
             }
         });
 
+        // Never build the prefab on top of the player or the exit
+        if dimensions.point_in_rect(mb.player_start)
+            || dimensions.point_in_rect(mb.teleportation_crystal_start)
+        {
+            can_place = false;
+        }
+
+        // Its doors could open onto solid rock, leaving the inside (and maybe the crystal) out of reach
+        if can_place && !reachable(mb, &tiles, Point::new(dimensions.x1, dimensions.y1)) {
+            can_place = false;
+        }
+
         if can_place {
             placement = Some(Point::new(dimensions.x1, dimensions.y1));
             // Remove monster spawns that were inside the prefab points
@@ -76,33 +217,113 @@ pub fn apply_prefab(
 
     // Is the placement Option not None?
     if let Some(placement) = placement {
-        // Filter out enters and new lines from our map string
-        let string_vec: Vec<char> = prefab
-            .0
-            .chars()
-            .filter(|a| *a != '\r' && *a != '\n' && !a.is_whitespace())
-            .collect();
-
-        let mut i = 0;
-        for ty in placement.y..placement.y + prefab.2 {
-            for tx in placement.x..placement.x + prefab.1 {
-                let idx = map_idx(tx, ty);
-                let c = string_vec[i];
+        for (y, row) in tiles.iter().enumerate() {
+            for (x, c) in row.iter().enumerate() {
+                let pt = placement + Point::new(x, y);
+                let idx = mb.map.map_idx(pt.x, pt.y);
+                mb.map.tiles[idx] = vault_tile(*c);
                 match c {
-                    'M' => {
-                        mb.map.tiles[idx] = TileType::Floor;
-                        mb.monster_spawns.push(Point::new(tx, ty));
-                    }
-                    'X' => {
-                        mb.map.tiles[idx] = TileType::Floor;
-                        mb.guaranteed_monster_spawns.push(Point::new(tx, ty));
-                    }
-                    '-' => mb.map.tiles[idx] = TileType::Floor,
-                    '#' => mb.map.tiles[idx] = TileType::Wall,
-                    _ => println!("apply_prefab doesn't know what to do with token: [{}]", c),
+                    'M' => mb.monster_spawns.push(pt),
+                    'X' => mb.guaranteed_monster_spawns.push(pt),
+                    '#' | '+' | '-' => {}
+                    // Simulation turns this into the exit or the crystal, whichever the level has
+                    '>' | '*' => mb.teleportation_crystal_start = pt,
+                    // Unknown glyphs never make it past load_vaults, so this is from the legend
+                    _ => mb.template_spawns.push((pt, vault.legend[c].clone())),
                 }
-                i += 1;
             }
         }
     }
 }
+
+// What the map holds under each glyph, everything but walls and doors is floor with something on top
+fn vault_tile(glyph: char) -> TileType {
+    match glyph {
+        '#' => TileType::Wall,
+        '+' => TileType::DoorClosed,
+        _ => TileType::Floor,
+    }
+}
+
+// Stamps the vault on a copy of the map and checks the player can get to every tile of it that isn't a wall
+fn reachable(mb: &MapBuilder, tiles: &[Vec<char>], placement: Point) -> bool {
+    let mut map = mb.map.clone();
+    let mut open = Vec::new();
+    for (y, row) in tiles.iter().enumerate() {
+        for (x, c) in row.iter().enumerate() {
+            let pt = placement + Point::new(x, y);
+            let idx = map.map_idx(pt.x, pt.y);
+            map.tiles[idx] = vault_tile(*c);
+            if *c != '#' {
+                open.push(idx);
+            }
+        }
+    }
+    let dijkstra_map = DijkstraMap::new(
+        map.width,
+        map.height,
+        &[map.point2d_to_index(mb.player_start)],
+        &map,
+        1024.0,
+    );
+    open.iter().all(|idx| dijkstra_map.map[*idx] < f32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::rooms::RoomArchitect;
+    use super::super::MapArchitect;
+    use super::*;
+
+    fn crystal_arena() -> Vault {
+        let templates = Templates::load().unwrap();
+        VaultLibrary::load(&templates)
+            .unwrap()
+            .vaults
+            .into_iter()
+            .find(|vault| vault.name == "crystal_arena")
+            .unwrap()
+    }
+
+    #[test]
+    fn validate_lists_every_problem() {
+        let vault = Vault {
+            name: "broken".to_string(),
+            weight: 0,
+            depths: HashSet::new(),
+            themes: HashSet::new(),
+            rows: vec!["#?#".to_string(), "#-".to_string()],
+            legend: [('O', "Nobody".to_string())].into_iter().collect(),
+        };
+        let problems = vault.validate(&Templates::load().unwrap());
+        assert_eq!(problems.len(), 4, "{:?}", problems);
+    }
+
+    #[test]
+    fn placed_crystal_can_be_reached() {
+        let vault = crystal_arena();
+        for seed in 0..100 {
+            let mut rng = RandomNumberGenerator::seeded(seed);
+            let mut mb = RoomArchitect { rooms: Vec::new() }.build(&mut rng, MAP_WIDTH, MAP_HEIGHT);
+            mb.teleportation_crystal_start = mb.find_most_distant();
+            let before = mb.teleportation_crystal_start;
+            apply_prefab(&mut mb, &mut rng, &vault);
+            if mb.teleportation_crystal_start == before {
+                continue;
+            }
+            let dijkstra_map = DijkstraMap::new(
+                mb.map.width,
+                mb.map.height,
+                &[mb.map.point2d_to_index(mb.player_start)],
+                &mb.map,
+                1024.0,
+            );
+            let idx = mb.map.point2d_to_index(mb.teleportation_crystal_start);
+            assert!(
+                dijkstra_map.map[idx] < f32::MAX,
+                "seed {} left the crystal out of reach",
+                seed
+            );
+        }
+    }
+}
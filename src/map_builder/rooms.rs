@@ -1,34 +1,31 @@
 use super::MapArchitect;
 use crate::prelude::*;
 
+// How many rooms a screen sized map gets, smaller and bigger maps get fewer or more
 const NUM_ROOMS: usize = 30;
+// Small maps fill up before they have all their rooms, give up on placing more after this many tries
+const MAX_ATTEMPTS: usize = 1000;
 
 pub struct RoomArchitect {
     pub rooms: Vec<Rect>,
 }
 
 impl RoomArchitect {
-    fn carve_decorations(&mut self, rng: &mut RandomNumberGenerator, map: &mut Map) {
-        // Turn some floor tiles into decorated, complete 50/50 chance
-        map.tiles
-            .iter_mut()
-            .filter(|t| (**t == TileType::Floor && rng.range(0, 11) > 5))
-            .for_each(|t| *t = TileType::Floor2);
-
-        // Turn some wall tiles into decorated, favor-non decorated wall tiles
-        map.tiles
-            .iter_mut()
-            .filter(|t| (**t == TileType::Wall && rng.range(0, 11) > 6))
-            .for_each(|t| *t = TileType::Wall2);
-    }
-
     fn build_random_rooms(&mut self, rng: &mut RandomNumberGenerator, map: &mut Map) {
-        while self.rooms.len() < NUM_ROOMS {
+        let num_rooms = (NUM_ROOMS * (map.width * map.height) as usize
+            / (SCREEN_WIDTH * SCREEN_HEIGHT) as usize)
+            .max(1);
+        let mut attempts = 0;
+        while self.rooms.len() < num_rooms && attempts < MAX_ATTEMPTS {
+            attempts += 1;
+            // Rooms keep a tile of wall to the map's edge, on a small map that makes them smaller too
+            let x = rng.range(1, (map.width - 6).max(2));
+            let y = rng.range(1, (map.height - 6).max(2));
             let room = Rect::with_size(
-                rng.range(1, SCREEN_WIDTH - 6),
-                rng.range(1, SCREEN_HEIGHT - 6),
-                rng.range(2, 6),
-                rng.range(2, 6),
+                x,
+                y,
+                rng.range(2, 6).min(map.width - 1 - x).max(1),
+                rng.range(2, 6).min(map.height - 1 - y).max(1),
             );
 
             // Check if this new randomly generated room intersects an existing
@@ -44,7 +41,7 @@ impl RoomArchitect {
                 // Loop through and change the actual map to floor TileType
                 room.for_each(|p: Point| {
                     if map.in_bounds(p) {
-                        let idx = map_idx(p.x, p.y);
+                        let idx = map.map_idx(p.x, p.y);
                         map.tiles[idx] = TileType::Floor;
                     }
                 });
@@ -59,7 +56,7 @@ impl RoomArchitect {
         let mut rooms = self.rooms.clone();
         // Vectors include a sort_by to sort elements.
         // It takes a closure (lambda) which calls the cmp function
-        rooms.sort_by(|a, b| a.center().x.cmp(&b.center().x));
+        rooms.sort_by_key(|a| a.center().x);
 
         // Iterate over the rooms, skip first
         for (i, room) in rooms.iter().enumerate().skip(1) {
@@ -85,7 +82,7 @@ impl RoomArchitect {
             // Probe map to see if x,y exists as index
             if let Some(idx) = map.try_idx(Point::new(x, y)) {
                 // if so turn it into floor
-                map.tiles[idx as usize] = TileType::Floor;
+                map.tiles[idx] = TileType::Floor;
             }
         }
     }
@@ -95,34 +92,38 @@ impl RoomArchitect {
         use std::cmp::{max, min};
         for x in min(x1, x2)..=max(x1, x2) {
             if let Some(idx) = map.try_idx(Point::new(x, y)) {
-                map.tiles[idx as usize] = TileType::Floor;
+                map.tiles[idx] = TileType::Floor;
             }
         }
     }
 }
 
 impl MapArchitect for RoomArchitect {
-    fn new(&mut self, rng: &mut RandomNumberGenerator) -> MapBuilder {
-        let mut mb = MapBuilder {
-            map: Map::new(),
-            monster_spawns: Vec::new(),
-            player_start: Point::zero(),
-            guaranteed_monster_spawns: Vec::new(),
-            teleportation_crystal_start: Point::zero(),
-            theme: super::themes::DungeonTheme::new(),
-        };
+    fn build(&mut self, rng: &mut RandomNumberGenerator, width: i32, height: i32) -> MapBuilder {
+        let mut mb = MapBuilder::from_map(Map::new(width, height));
 
         mb.fill(TileType::Wall);
         self.build_random_rooms(rng, &mut mb.map);
         self.build_corridors(rng, &mut mb.map);
         mb.player_start = self.rooms[0].center();
-        mb.teleportation_crystal_start = mb.find_most_distant();
-        for room in self.rooms.iter().skip(1) {
-            mb.monster_spawns.push(room.center());
-        }
-
-        self.carve_decorations(rng, &mut mb.map);
+        mb.rooms = self.rooms.clone();
 
         mb
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_on_small_maps() {
+        for (width, height) in [(20, 15), (8, 8), (5, 4), (3, 3)] {
+            for seed in 0..50 {
+                let mut rng = RandomNumberGenerator::seeded(seed);
+                let mb = RoomArchitect { rooms: Vec::new() }.build(&mut rng, width, height);
+                assert!(mb.map.can_enter_tile(mb.player_start));
+            }
+        }
+    }
+}
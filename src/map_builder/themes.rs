@@ -1,25 +1,91 @@
 use crate::prelude::*;
+use ron::de::from_str;
+use serde::Deserialize;
+use std::collections::HashMap;
 
-pub struct DungeonTheme {}
-pub struct ForestTheme {}
-pub struct CaveTheme {}
+// How a single tile type is drawn, colours are (r, g, b)
+#[derive(Clone, Deserialize, Debug)]
+pub struct TileStyle {
+    pub glyph: char,
+    pub fg: (u8, u8, u8),
+    pub bg: (u8, u8, u8),
+    // Extra glyphs that show up in place of the main one here and there, each as often as the main one
+    #[serde(default)]
+    pub variants: Vec<char>,
+}
 
-impl DungeonTheme {
-    pub fn new() -> Box<dyn MapTheme> {
-        Box::new(Self {})
-    }
+// This struct is reflected in themes.ron, a new biome is just another entry in that file
+#[derive(Clone, Default, Deserialize, Debug)]
+pub struct DataTheme {
+    pub name: String,
+    pub tiles: HashMap<TileType, TileStyle>,
 }
 
-impl ForestTheme {
-    pub fn new() -> Box<dyn MapTheme> {
-        Box::new(Self {})
-    }
+// Parsed once for the whole run and kept as a resource, like the templates and vaults
+#[derive(Clone, Default, Deserialize, Debug)]
+pub struct ThemeLibrary {
+    pub themes: Vec<DataTheme>,
 }
 
-impl CaveTheme {
-    pub fn new() -> Box<dyn MapTheme> {
-        Box::new(Self {})
+impl ThemeLibrary {
+    pub fn load() -> Result<Self, Vec<String>> {
+        let library: Self = from_str(&data_file("themes.ron", THEME_DATA))
+            .map_err(|e| vec![format!("Unable to parse themes.ron: {}", e)])?;
+        let problems = library.validate();
+        if problems.is_empty() {
+            Ok(library)
+        } else {
+            Err(problems)
+        }
     }
+
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !self.has("dungeon") {
+            problems.push("themes.ron needs a dungeon theme to fall back on".to_string());
+        }
+        for (i, theme) in self.themes.iter().enumerate() {
+            if self.themes[..i]
+                .iter()
+                .any(|other| other.name == theme.name)
+            {
+                problems.push(format!(
+                    "{}: there is more than one theme by that name",
+                    theme.name
+                ));
+            }
+        }
+        problems
+    }
+
+    pub fn has(&self, name: &str) -> bool {
+        self.themes.iter().any(|theme| theme.name == name)
+    }
+
+    // Looks up a theme from its name, unknown names fall back to the dungeon
+    // Without even that (broken themes.ron, see data_problems) every tile shows up as a '?'
+    pub fn theme(&self, name: &str) -> Box<dyn MapTheme> {
+        let theme = self
+            .themes
+            .iter()
+            .find(|theme| theme.name == name)
+            .or_else(|| self.themes.iter().find(|theme| theme.name == "dungeon"))
+            .cloned()
+            .unwrap_or_else(|| DataTheme {
+                name: name.to_string(),
+                ..Default::default()
+            });
+        Box::new(theme)
+    }
+}
+
+// Scrambles the tile index so the variants don't line up in stripes
+// It has to give the same answer every frame, otherwise the map would flicker
+fn variant_roll(idx: usize) -> usize {
+    let mut x = (idx as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (x ^ (x >> 31)) as usize
 }
 
 // Note here:
@@ -28,40 +94,27 @@ impl CaveTheme {
 // pure functions are always safe to use in a multi-threaded context, since there is nothing to sync between threads
 // if you do need to store state, investigate 'syncronization primitives' in particular 'Mutex' and 'Atomic'
 
-/* MAP THEME TRAIT IMPLEMENTATIONS STARTS HERE */
-
-impl MapTheme for DungeonTheme {
-    fn tile_to_render(&self, tile_type: TileType) -> FontCharType {
-        match tile_type {
-            TileType::Floor | TileType::Floor3 => to_cp437('L'),
-            TileType::Floor2 => to_cp437('M'),
-            TileType::Wall => to_cp437(']'),
-            TileType::Wall2 => to_cp437('#'),
-            TileType::Exit => to_cp437('>'),
-        }
-    }
-}
-
-impl MapTheme for ForestTheme {
-    fn tile_to_render(&self, tile_type: TileType) -> FontCharType {
-        match tile_type {
-            TileType::Floor => to_cp437('='),
-            TileType::Floor2 => to_cp437(';'),
-            TileType::Floor3 => to_cp437('<'),
-            TileType::Wall | TileType::Wall2 => to_cp437('"'),
-            TileType::Exit => to_cp437('>'),
+impl MapTheme for DataTheme {
+    fn tile_to_render(&self, tile_type: TileType, idx: usize) -> (FontCharType, ColorPair) {
+        match self.tiles.get(&tile_type) {
+            Some(style) => {
+                let roll = variant_roll(idx) % (style.variants.len() + 1);
+                let glyph = if roll == 0 {
+                    style.glyph
+                } else {
+                    style.variants[roll - 1]
+                };
+                (
+                    to_cp437(glyph),
+                    ColorPair::new(RGB::named(style.fg), RGB::named(style.bg)),
+                )
+            }
+            // A theme that forgot about a tile type still shows something we can spot
+            None => (to_cp437('?'), ColorPair::new(MAGENTA, BLACK)),
         }
     }
-}
 
-impl MapTheme for CaveTheme {
-    fn tile_to_render(&self, tile_type: TileType) -> FontCharType {
-        match tile_type {
-            TileType::Floor => to_cp437('-'),
-            TileType::Floor2 | TileType::Floor3 => to_cp437(','),
-            TileType::Wall => to_cp437('\\'),
-            TileType::Wall2 => to_cp437('$'),
-            TileType::Exit => to_cp437('>'),
-        }
+    fn name(&self) -> &str {
+        &self.name
     }
 }
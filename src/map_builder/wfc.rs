@@ -0,0 +1,295 @@
+use super::MapArchitect;
+use crate::prelude::*;
+
+// Hand drawn sample for the caves, same format as the prefabs: '#' is wall and everything else floor
+pub const CAVE_SAMPLE: &str = include_str!("../../resources/samples/caves.txt");
+pub const FORTRESS_SAMPLE: &str = include_str!("../../resources/samples/fortress.txt");
+
+// Look up a sample from its name in the campaign file, None for names we don't have a sample for
+pub fn sample_by_name(name: &str) -> Option<&'static str> {
+    match name {
+        "caves" => Some(CAVE_SAMPLE),
+        "fortress" => Some(FORTRESS_SAMPLE),
+        _ => None,
+    }
+}
+
+// Width and height of the chunks we cut out of the sample and stitch together again
+const CHUNK_SIZE: i32 = 3;
+// How many times we start over when the collapse runs into a dead end, or leaves too little to walk on
+const MAX_ATTEMPTS: usize = 10;
+
+// Wave function collapse: learn which chunks of the sample may sit next to each other, then fill the
+// map chunk by chunk, always collapsing the most constrained spot first
+pub struct WfcArchitect {
+    pub sample: &'static str,
+}
+
+// A chunk of the sample and how often it showed up, the more common chunks get picked more often
+struct Pattern {
+    tiles: Vec<TileType>,
+    frequency: usize,
+}
+
+// Neighbours of a chunk on the output grid, in the same order as the compatibility lists
+const DIRECTIONS: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
+
+impl WfcArchitect {
+    // Turns the sample text into a map so we can read chunks out of it with the usual helpers
+    fn read_sample(&self) -> Map {
+        let rows: Vec<&str> = self
+            .sample
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty())
+            .collect();
+        let width = rows.iter().map(|row| row.len()).min().unwrap_or(0) as i32;
+        let mut sample = Map::new(width, rows.len() as i32);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().take(width as usize).enumerate() {
+                let idx = sample.map_idx(x as i32, y as i32);
+                sample.tiles[idx] = if c == '#' {
+                    TileType::Wall
+                } else {
+                    TileType::Floor
+                };
+            }
+        }
+        sample
+    }
+
+    // Every CHUNK_SIZE square of the sample, overlapping ones included, counting duplicates
+    fn build_patterns(&self, sample: &Map) -> Vec<Pattern> {
+        let mut patterns: Vec<Pattern> = Vec::new();
+        for y in 0..=sample.height - CHUNK_SIZE {
+            for x in 0..=sample.width - CHUNK_SIZE {
+                let mut tiles = Vec::new();
+                for dy in 0..CHUNK_SIZE {
+                    for dx in 0..CHUNK_SIZE {
+                        tiles.push(sample.tiles[sample.map_idx(x + dx, y + dy)]);
+                    }
+                }
+                // Keep these in a plain Vec, a HashMap would shuffle the order and break our seeds
+                match patterns.iter_mut().find(|p| p.tiles == tiles) {
+                    Some(pattern) => pattern.frequency += 1,
+                    None => patterns.push(Pattern {
+                        tiles,
+                        frequency: 1,
+                    }),
+                }
+            }
+        }
+        patterns
+    }
+
+    // The row or column of a chunk facing the given direction
+    fn edge(pattern: &Pattern, direction: usize) -> Vec<TileType> {
+        (0..CHUNK_SIZE)
+            .map(|i| {
+                let (x, y) = match direction {
+                    0 => (i, 0),
+                    1 => (CHUNK_SIZE - 1, i),
+                    2 => (i, CHUNK_SIZE - 1),
+                    _ => (0, i),
+                };
+                pattern.tiles[(y * CHUNK_SIZE + x) as usize]
+            })
+            .collect()
+    }
+
+    // compatible[a][direction][b]: may chunk b sit next to chunk a in that direction?
+    // Two chunks fit when the edges that touch are the same
+    fn build_compatibility(&self, patterns: &[Pattern]) -> Vec<Vec<Vec<bool>>> {
+        patterns
+            .iter()
+            .map(|a| {
+                (0..DIRECTIONS.len())
+                    .map(|direction| {
+                        let edge = Self::edge(a, direction);
+                        let opposite = (direction + 2) % 4;
+                        patterns
+                            .iter()
+                            .map(|b| Self::edge(b, opposite) == edge)
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Runs a single collapse over a grid of chunks, returns the pattern picked for every cell
+    // or None if some cell ended up without any options
+    fn collapse(
+        &self,
+        rng: &mut RandomNumberGenerator,
+        patterns: &[Pattern],
+        compatible: &[Vec<Vec<bool>>],
+        grid_width: i32,
+        grid_height: i32,
+    ) -> Option<Vec<usize>> {
+        if patterns.is_empty() {
+            return None;
+        }
+        let cells = (grid_width * grid_height) as usize;
+        // Every cell starts out being able to become any of the patterns
+        let mut options: Vec<Vec<usize>> = vec![(0..patterns.len()).collect(); cells];
+
+        loop {
+            // Find the undecided cell with the fewest options left
+            let fewest = options
+                .iter()
+                .map(|o| o.len())
+                .filter(|len| *len > 1)
+                .min();
+            let fewest = match fewest {
+                Some(fewest) => fewest,
+                None => break,
+            };
+            let candidates: Vec<usize> = options
+                .iter()
+                .enumerate()
+                .filter(|(_, o)| o.len() == fewest)
+                .map(|(i, _)| i)
+                .collect();
+            let cell = *rng.random_slice_entry(&candidates).unwrap();
+
+            // Collapse it to one of its patterns, weighted by how common the pattern is in the sample
+            let total: usize = options[cell].iter().map(|p| patterns[*p].frequency).sum();
+            let mut roll = rng.range(0, total);
+            let mut chosen = options[cell][0];
+            for p in options[cell].iter() {
+                if roll < patterns[*p].frequency {
+                    chosen = *p;
+                    break;
+                }
+                roll -= patterns[*p].frequency;
+            }
+            options[cell] = vec![chosen];
+
+            // Spread the decision out to the neighbours, and their neighbours and so on
+            let mut to_visit = vec![cell];
+            while let Some(current) = to_visit.pop() {
+                let x = current as i32 % grid_width;
+                let y = current as i32 / grid_width;
+                for (direction, (dx, dy)) in DIRECTIONS.iter().enumerate() {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || ny < 0 || nx >= grid_width || ny >= grid_height {
+                        continue;
+                    }
+                    let neighbour = (ny * grid_width + nx) as usize;
+                    let before = options[neighbour].len();
+                    let allowed = &options[current];
+                    let remaining: Vec<usize> = options[neighbour]
+                        .iter()
+                        .filter(|b| allowed.iter().any(|a| compatible[*a][direction][**b]))
+                        .copied()
+                        .collect();
+                    if remaining.is_empty() {
+                        return None;
+                    }
+                    if remaining.len() < before {
+                        options[neighbour] = remaining;
+                        to_visit.push(neighbour);
+                    }
+                }
+            }
+        }
+
+        Some(options.iter().map(|o| o[0]).collect())
+    }
+
+    // Same as the cellular automata, start as close to the center as we can get
+    fn find_start(&self, map: &Map) -> Option<Point> {
+        let center = Point::new(map.width / 2, map.height / 2);
+        map.tiles
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| **t == TileType::Floor)
+            .map(|(idx, _)| map.index_to_point2d(idx))
+            .min_by(|a, b| {
+                DistanceAlg::Pythagoras
+                    .distance2d(center, *a)
+                    .partial_cmp(&DistanceAlg::Pythagoras.distance2d(center, *b))
+                    .unwrap()
+            })
+    }
+
+    // Walls off every floor tile we can't walk to from the start, returns how many are left
+    fn cull_unreachable(&self, map: &mut Map, start: Point) -> usize {
+        let dijkstra_map = DijkstraMap::new(
+            map.width,
+            map.height,
+            &[map.point2d_to_index(start)],
+            &*map,
+            1024.0,
+        );
+        let mut reachable = 0;
+        for (idx, distance) in dijkstra_map.map.iter().enumerate() {
+            if map.tiles[idx] == TileType::Floor {
+                if *distance == f32::MAX {
+                    map.tiles[idx] = TileType::Wall;
+                } else {
+                    reachable += 1;
+                }
+            }
+        }
+        reachable
+    }
+
+    // Paints the chosen chunks onto the map, leaving a solid wall around the edge
+    fn paint(&self, map: &mut Map, patterns: &[Pattern], chosen: &[usize], grid_width: i32) {
+        map.tiles.iter_mut().for_each(|t| *t = TileType::Wall);
+        for (cell, pattern) in chosen.iter().enumerate() {
+            let cx = cell as i32 % grid_width * CHUNK_SIZE + 1;
+            let cy = cell as i32 / grid_width * CHUNK_SIZE + 1;
+            for dy in 0..CHUNK_SIZE {
+                for dx in 0..CHUNK_SIZE {
+                    let idx = map.map_idx(cx + dx, cy + dy);
+                    map.tiles[idx] = patterns[*pattern].tiles[(dy * CHUNK_SIZE + dx) as usize];
+                }
+            }
+        }
+    }
+}
+
+impl MapArchitect for WfcArchitect {
+    fn build(&mut self, rng: &mut RandomNumberGenerator, width: i32, height: i32) -> MapBuilder {
+        let mut mb = MapBuilder::from_map(Map::new(width, height));
+
+        let sample = self.read_sample();
+        let patterns = self.build_patterns(&sample);
+        let compatible = self.build_compatibility(&patterns);
+        // Keep the outer ring of the map free for the border wall
+        let grid_width = (width - 2) / CHUNK_SIZE;
+        let grid_height = (height - 2) / CHUNK_SIZE;
+
+        let mut start = None;
+        for _ in 0..MAX_ATTEMPTS {
+            mb.fill(TileType::Wall);
+            if let Some(chosen) =
+                self.collapse(rng, &patterns, &compatible, grid_width, grid_height)
+            {
+                self.paint(&mut mb.map, &patterns, &chosen, grid_width);
+            }
+            start = self.find_start(&mb.map);
+            if let Some(start) = start {
+                // A good level lets you walk around a fair share of the map
+                if self.cull_unreachable(&mut mb.map, start) >= mb.map.tiles.len() / 4 {
+                    break;
+                }
+            }
+        }
+
+        // Never happens with a sensible sample, but a level needs at least somewhere to stand
+        let start = start.unwrap_or_else(|| {
+            let center = Point::new(width / 2, height / 2);
+            let idx = mb.map.point2d_to_index(center);
+            mb.map.tiles[idx] = TileType::Floor;
+            center
+        });
+
+        mb.player_start = start;
+
+        mb
+    }
+}
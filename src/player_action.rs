@@ -0,0 +1,58 @@
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Everything the player can do on their turn, independent of which key (if any) asked for it
+// player_input_system reads this as a resource so the game can be driven without a window
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PlayerAction {
+    Move(Point), // Direction to move or attack in
+    PickUp,
+    UseItem(usize),          // Index into the inventory
+    UseItemAt(usize, Point), // Same for items with an Area effect, along with the map position picked for it
+    Drop(usize),             // Index into the inventory, the item is left on the player's tile
+    Wait,
+    Fire(Point), // Map position to shoot at with a ranged weapon, picked while TurnState::Targeting
+    LevelUp(LevelUpChoice), // Only while TurnState::LevelUp, it doesn't spend a turn
+}
+
+// What a level up can improve on the drone
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LevelUpChoice {
+    Health,
+    Damage,
+    Sight,
+}
+
+impl PlayerAction {
+    // Any key we don't have a use for spends the turn waiting
+    pub fn from_key(key: VirtualKeyCode) -> Self {
+        match key {
+            VirtualKeyCode::Left | VirtualKeyCode::A => PlayerAction::Move(Point::new(-1, 0)),
+            VirtualKeyCode::Right | VirtualKeyCode::D => PlayerAction::Move(Point::new(1, 0)),
+            VirtualKeyCode::Up | VirtualKeyCode::W => PlayerAction::Move(Point::new(0, -1)),
+            VirtualKeyCode::Down | VirtualKeyCode::S => PlayerAction::Move(Point::new(0, 1)),
+            VirtualKeyCode::G => PlayerAction::PickUp,
+            // Alot of ways to consume items
+            VirtualKeyCode::Key1 => PlayerAction::UseItem(0),
+            VirtualKeyCode::Key2 => PlayerAction::UseItem(1),
+            VirtualKeyCode::Key3 => PlayerAction::UseItem(2),
+            VirtualKeyCode::Key4 => PlayerAction::UseItem(3),
+            VirtualKeyCode::Key5 => PlayerAction::UseItem(4),
+            VirtualKeyCode::Key6 => PlayerAction::UseItem(5),
+            VirtualKeyCode::Key7 => PlayerAction::UseItem(6),
+            VirtualKeyCode::Key8 => PlayerAction::UseItem(7),
+            VirtualKeyCode::Key9 => PlayerAction::UseItem(8),
+            _ => PlayerAction::Wait,
+        }
+    }
+
+    // The level up screen has keys of its own, anything else leaves the choice open
+    pub fn level_up_from_key(key: VirtualKeyCode) -> Option<Self> {
+        match key {
+            VirtualKeyCode::Key1 => Some(PlayerAction::LevelUp(LevelUpChoice::Health)),
+            VirtualKeyCode::Key2 => Some(PlayerAction::LevelUp(LevelUpChoice::Damage)),
+            VirtualKeyCode::Key3 => Some(PlayerAction::LevelUp(LevelUpChoice::Sight)),
+            _ => None,
+        }
+    }
+}
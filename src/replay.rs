@@ -0,0 +1,33 @@
+// Records every accepted player action of a run so the run can be played back turn by turn
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+
+pub const REPLAY_FILE: &str = "replay.ron";
+
+// The seed rebuilds the exact same levels and monsters, the actions repeat what the player did with them
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: RunSeed,
+    pub actions: Vec<PlayerAction>,
+}
+
+impl Replay {
+    pub fn new(seed: RunSeed) -> Self {
+        Self {
+            seed,
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(ron::de::from_str(&contents)?)
+    }
+
+    pub fn write(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        std::fs::write(path, ron::ser::to_string(self)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+// Handles writing a run to disk and reading it back again
+
+use crate::prelude::*;
+use legion::serialize::UnknownType;
+use serde::de::{self, DeserializeSeed, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+
+const SAVE_FILE: &str = "savegame.ron";
+
+// Every resource that makes up a run, the entities themselves are stored next to this in the save file
+// Cow lets us serialize straight from borrowed resources and still deserialize into owned values
+#[derive(Serialize, Deserialize)]
+struct SavedResources<'a> {
+    map: Cow<'a, Map>,
+    camera: Cow<'a, Camera>,
+    turn_state: TurnState,
+    theme: String,
+    event_log: Cow<'a, EventLog>,
+    seed: RunSeed,
+    rng: Cow<'a, RandomNumberGenerator>,
+    replay: Cow<'a, Replay>,
+    visited_levels: Cow<'a, VisitedLevels>,
+}
+
+// Legion needs to know about every component type we want to keep, and a stable name for each of them
+// Message components (WantsToMove etc) only live for a single turn so they are left out on purpose
+fn registry() -> Registry<String> {
+    let mut registry = Registry::<String>::default();
+    registry.on_unknown(UnknownType::Ignore);
+    registry.register::<Point>("point".to_string());
+    registry.register::<Render>("render".to_string());
+    registry.register::<Health>("health".to_string());
+    registry.register::<Damage>("damage".to_string());
+    registry.register::<Accuracy>("accuracy".to_string());
+    registry.register::<Evasion>("evasion".to_string());
+    registry.register::<Weapon>("weapon".to_string());
+    registry.register::<Ranged>("ranged".to_string());
+    registry.register::<Cooldown>("cooldown".to_string());
+    registry.register::<Ammo>("ammo".to_string());
+    registry.register::<Defence>("defence".to_string());
+    registry.register::<Armour>("armour".to_string());
+    registry.register::<Name>("name".to_string());
+    registry.register::<Player>("player".to_string());
+    registry.register::<Progress>("progress".to_string());
+    registry.register::<GrantsXp>("grants_xp".to_string());
+    registry.register::<Enemy>("enemy".to_string());
+    registry.register::<MovingRandomly>("moving_randomly".to_string());
+    registry.register::<ChasingPlayer>("chasing_player".to_string());
+    registry.register::<MoveEveryOther>("move_every_other".to_string());
+    registry.register::<Item>("item".to_string());
+    registry.register::<TelerportationCrystal>("teleportation_crystal".to_string());
+    registry.register::<FieldOfView>("field_of_view".to_string());
+    registry.register::<Provides>("provides".to_string());
+    registry.register::<OnHit>("on_hit".to_string());
+    registry.register::<Hazard>("hazard".to_string());
+    registry.register::<Carried>("carried".to_string());
+    registry.register::<Statuses>("statuses".to_string());
+    registry.register::<OtherLevelPosition>("other_level_position".to_string());
+    registry
+}
+
+pub fn has_save() -> bool {
+    std::path::Path::new(SAVE_FILE).exists()
+}
+
+// Writes the world and all of our resources into the save file, replacing any earlier save
+pub fn save_game(ecs: &World, resources: &Resources) -> Result<(), Box<dyn Error>> {
+    std::fs::write(SAVE_FILE, to_ron(ecs, resources)?)?;
+    Ok(())
+}
+
+// Reads the save file back into a fresh world and set of resources
+pub fn load_game() -> Result<(World, Resources), Box<dyn Error>> {
+    from_ron(&std::fs::read_to_string(SAVE_FILE)?)
+}
+
+fn to_ron(ecs: &World, resources: &Resources) -> Result<String, Box<dyn Error>> {
+    let registry = registry();
+    let map = resources.get::<Map>().unwrap();
+    let camera = resources.get::<Camera>().unwrap();
+    let turn_state = resources.get::<TurnState>().unwrap();
+    let theme = resources.get::<Box<dyn MapTheme>>().unwrap();
+    let event_log = resources.get::<EventLog>().unwrap();
+    let seed = resources.get::<RunSeed>().unwrap();
+    let rng = resources.get::<RandomNumberGenerator>().unwrap();
+    let replay = resources.get::<Replay>().unwrap();
+    let visited_levels = resources.get::<VisitedLevels>().unwrap();
+
+    let saved = SavedResources {
+        map: Cow::Borrowed(&map),
+        camera: Cow::Borrowed(&camera),
+        turn_state: *turn_state,
+        theme: theme.name().to_string(),
+        event_log: Cow::Borrowed(&event_log),
+        seed: *seed,
+        rng: Cow::Borrowed(&rng),
+        replay: Cow::Borrowed(&replay),
+        visited_levels: Cow::Borrowed(&visited_levels),
+    };
+
+    // The save file is a tuple of (resources, world)
+    Ok(ron::ser::to_string(&(
+        saved,
+        ecs.as_serializable(any(), &registry),
+    ))?)
+}
+
+fn from_ron(contents: &str) -> Result<(World, Resources), Box<dyn Error>> {
+    let registry = registry();
+    let mut deserializer = ron::de::Deserializer::from_str(contents)?;
+    let (saved, ecs) = SaveFileSeed {
+        registry: &registry,
+    }
+    .deserialize(&mut deserializer)?;
+
+    let mut resources = Resources::default();
+    resources.insert(saved.map.into_owned());
+    resources.insert(saved.camera.into_owned());
+    resources.insert(saved.turn_state);
+    resources.insert(saved.event_log.into_owned());
+    resources.insert(saved.seed);
+    resources.insert(saved.rng.into_owned());
+    resources.insert(saved.replay.into_owned());
+    resources.insert(saved.visited_levels.into_owned());
+    // The campaign, templates, vaults and themes come from the game's data, not the save
+    insert_game_data(&mut resources);
+    let theme = resources.get::<ThemeLibrary>().unwrap().theme(&saved.theme);
+    resources.insert(theme);
+
+    Ok((ecs, resources))
+}
+
+// A finished run (dead or victorious) shouldn't be resumable
+pub fn delete_save() -> Result<(), Box<dyn Error>> {
+    if has_save() {
+        std::fs::remove_file(SAVE_FILE)?;
+    }
+    Ok(())
+}
+
+// The world can't simply derive Deserialize since it needs the registry to know the component types
+// so we walk the (resources, world) tuple ourselves and hand the registry over for the second half
+#[derive(Clone, Copy)]
+struct SaveFileSeed<'a> {
+    registry: &'a Registry<String>,
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for SaveFileSeed<'a> {
+    type Value = (SavedResources<'static>, World);
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_tuple(2, self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for SaveFileSeed<'a> {
+    type Value = (SavedResources<'static>, World);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a tuple of saved resources and a world")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let saved = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let ecs = seq
+            .next_element_seed(self.registry.as_deserialize())?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+        Ok((saved, ecs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every registered component an entity has, carried items point at their carrier by name
+    // since the entities themselves get new ids when the world is read back
+    fn describe(ecs: &World) -> Vec<String> {
+        macro_rules! components {
+            ($entry:expr, $parts:expr, $($component:ty),*) => {
+                $(
+                    if let Ok(component) = $entry.get_component::<$component>() {
+                        $parts.push(format!("{:?}", component));
+                    }
+                )*
+            };
+        }
+        let name = |entity: Entity| {
+            ecs.entry_ref(entity).ok().and_then(|entry| {
+                entry
+                    .get_component::<Name>()
+                    .ok()
+                    .map(|name| name.0.clone())
+            })
+        };
+
+        let mut entities: Vec<String> = <Entity>::query()
+            .iter(ecs)
+            .map(|entity| {
+                let entry = ecs.entry_ref(*entity).unwrap();
+                let mut parts = vec![format!("{:?}", name(*entity))];
+                if let Ok(carried) = entry.get_component::<Carried>() {
+                    parts.push(format!("carried by {:?}", name(carried.0)));
+                }
+                components!(
+                    entry,
+                    parts,
+                    Point,
+                    Render,
+                    Health,
+                    Damage,
+                    Accuracy,
+                    Evasion,
+                    Weapon,
+                    Ranged,
+                    Cooldown,
+                    Ammo,
+                    Defence,
+                    Armour,
+                    Player,
+                    Progress,
+                    GrantsXp,
+                    Enemy,
+                    MovingRandomly,
+                    ChasingPlayer,
+                    MoveEveryOther,
+                    Item,
+                    TelerportationCrystal,
+                    Provides,
+                    OnHit,
+                    Hazard,
+                    Statuses,
+                    OtherLevelPosition
+                );
+                // The visible tiles are a set, so they have no order of their own
+                if let Ok(fov) = entry.get_component::<FieldOfView>() {
+                    let mut tiles: Vec<(i32, i32)> =
+                        fov.visible_tiles.iter().map(|pos| (pos.x, pos.y)).collect();
+                    tiles.sort_unstable();
+                    parts.push(format!("sees {:?} within {}", tiles, fov.radius));
+                }
+                parts.join(" ")
+            })
+            .collect();
+        entities.sort();
+        entities
+    }
+
+    #[test]
+    fn save_round_trip_keeps_the_run() {
+        let mut sim = Simulation::new(RunSeed(42));
+        for _ in 0..20 {
+            if sim.turn_state() == TurnState::AwaitingInput {
+                sim.play_turn(PlayerAction::Wait);
+            }
+        }
+
+        let saved = to_ron(&sim.ecs, &sim.resources).unwrap();
+        let (ecs, resources) = from_ron(&saved).unwrap();
+
+        assert_eq!(describe(&ecs), describe(&sim.ecs));
+        assert_eq!(
+            resources.get::<Map>().unwrap().tiles,
+            sim.resources.get::<Map>().unwrap().tiles
+        );
+        assert_eq!(
+            resources.get::<Replay>().unwrap().actions,
+            sim.resources.get::<Replay>().unwrap().actions
+        );
+        assert_eq!(
+            resources.get::<Box<dyn MapTheme>>().unwrap().name(),
+            sim.resources.get::<Box<dyn MapTheme>>().unwrap().name()
+        );
+        assert_eq!(
+            *resources.get::<RunSeed>().unwrap(),
+            *sim.resources.get::<RunSeed>().unwrap()
+        );
+    }
+}
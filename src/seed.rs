@@ -0,0 +1,18 @@
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// The seed the current run was started from, kept around as a resource so we can show it on the HUD
+// Every random roll in the game comes from the single RandomNumberGenerator resource seeded with this
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RunSeed(pub u64);
+
+impl RunSeed {
+    // Pick a brand new seed when the player didn't ask for a specific one
+    pub fn random() -> Self {
+        Self(RandomNumberGenerator::new().next_u64())
+    }
+
+    pub fn rng(&self) -> RandomNumberGenerator {
+        RandomNumberGenerator::seeded(self.0)
+    }
+}
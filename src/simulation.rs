@@ -0,0 +1,625 @@
+// The game logic without any window attached to it
+// Owns the world, our resources and the schedules that play out a turn, and is driven by PlayerActions
+
+use crate::prelude::*;
+
+pub struct Simulation {
+    // Collection of entities + components
+    pub ecs: World,
+    pub resources: Resources, // Shared data
+    // Various system collections
+    input_systems: Schedule,
+    player_systems: Schedule,
+    monster_systems: Schedule,
+}
+
+impl Simulation {
+    // Starts a brand new run on the first level
+    pub fn new(seed: RunSeed) -> Self {
+        let mut ecs = World::default();
+        let mut resources = Resources::default();
+        // Creates a map builder from which we grab our map
+        // Everything random in the run comes from this one generator, so the same seed gives the same run
+        let mut rng = seed.rng();
+        // The data files are parsed once here and handed to every level after this one as resources
+        insert_game_data(&mut resources);
+        let campaign = resources.get::<Campaign>().unwrap();
+        let templates = resources.get::<Templates>().unwrap();
+        let vaults = resources.get::<VaultLibrary>().unwrap();
+        let themes = resources.get::<ThemeLibrary>().unwrap();
+        let level = campaign.level(0);
+        let mut map_builder =
+            MapBuilder::new(&mut rng, 0, level, &vaults, &themes, MAP_WIDTH, MAP_HEIGHT);
+        spawn_player(&mut ecs, map_builder.player_start);
+        if level.teleportation_crystal {
+            spawn_telerportation_crystal(&mut ecs, map_builder.teleportation_crystal_start);
+        } else {
+            // For the love of god, SEAL THE EXITS - krieger
+            let exit_idx = map_builder
+                .map
+                .point2d_to_index(map_builder.teleportation_crystal_start);
+            map_builder.map.tiles[exit_idx] = TileType::Exit;
+        }
+        spawn_level(
+            &mut ecs,
+            &templates,
+            &mut rng,
+            0,
+            &map_builder.monster_spawns,
+            &map_builder.guaranteed_monster_spawns,
+            &map_builder.template_spawns,
+        );
+        // Hand the data back before we start inserting resources
+        drop((campaign, templates, vaults, themes));
+
+        // Inject our map and camera as resources (since this is what is shared in our program)
+        resources.insert(map_builder.map);
+        resources.insert(Camera::new(map_builder.player_start));
+        resources.insert(TurnState::AwaitingInput);
+        resources.insert(map_builder.theme);
+        resources.insert(EventLog::new());
+        resources.insert(rng);
+        resources.insert(seed);
+        resources.insert(Replay::new(seed));
+        resources.insert(VisitedLevels::new());
+
+        Self::from_parts(ecs, resources)
+    }
+
+    // Wraps an already existing world, for example one read from a save file
+    pub fn from_parts(ecs: World, mut resources: Resources) -> Self {
+        // Nobody is aiming at anything yet
+        resources.insert::<Option<Targeting>>(None);
+        Self {
+            ecs,
+            resources,
+            input_systems: build_input_scheduler(),
+            player_systems: build_player_scheduler(),
+            monster_systems: build_monster_scheduler(),
+        }
+    }
+
+    pub fn turn_state(&self) -> TurnState {
+        *self.resources.get::<TurnState>().unwrap()
+    }
+
+    // Executes the schedule for the current TurnState once
+    // The action is only looked at while we are awaiting input, None means the player hasn't decided yet
+    pub fn tick(&mut self, action: Option<PlayerAction>) {
+        // No need to worry about dupliactes, when we insert a resource of the same type
+        // It replaces if there is an already existing resource of the same type!
+        self.resources.insert(action);
+
+        // Execute systems, mutable borrow form ecs and resources!
+        // Depending on TurnState we execute different scheduler
+        match self.turn_state() {
+            TurnState::AwaitingInput => self
+                .input_systems
+                .execute(&mut self.ecs, &mut self.resources),
+            TurnState::PlayerTurn => self
+                .player_systems
+                .execute(&mut self.ecs, &mut self.resources),
+            TurnState::MonsterTurn => self
+                .monster_systems
+                .execute(&mut self.ecs, &mut self.resources),
+            TurnState::NextLevel => self.change_level(1),
+            TurnState::PreviousLevel => self.change_level(-1),
+            // Firing plays out like any other action, from the tile that was picked
+            TurnState::Targeting => {
+                if action.is_some() {
+                    self.stop_targeting();
+                    self.input_systems
+                        .execute(&mut self.ecs, &mut self.resources);
+                }
+            }
+            // Using or dropping something from the inventory screen spends the turn like always
+            TurnState::Inventory => {
+                if action.is_some() {
+                    self.resources.insert(TurnState::AwaitingInput);
+                    self.input_systems
+                        .execute(&mut self.ecs, &mut self.resources);
+                }
+            }
+            TurnState::LevelUp => {
+                if let Some(PlayerAction::LevelUp(choice)) = action {
+                    self.level_up(choice);
+                }
+            }
+            // Nothing to simulate in the menus
+            TurnState::Menu | TurnState::GameOver | TurnState::Victory => {}
+        }
+    }
+
+    // Plays a whole turn from the given action: the player acts, the monsters answer and we are back
+    // to awaiting input, unless the run ended on the way. Returns the TurnState we ended up in
+    pub fn play_turn(&mut self, action: PlayerAction) -> TurnState {
+        self.tick(Some(action));
+        loop {
+            match self.turn_state() {
+                TurnState::PlayerTurn
+                | TurnState::MonsterTurn
+                | TurnState::NextLevel
+                | TurnState::PreviousLevel => self.tick(None),
+                state => return state,
+            }
+        }
+    }
+
+    // Swaps in templates that changed while we were playing, or tells the player what is wrong with them
+    // Only what spawns from now on picks up the change, see respawn_level_entities for the rest
+    pub fn reload_templates(&mut self, templates: Result<Templates, Vec<String>>) {
+        let mut event_log = self.resources.get_mut::<EventLog>().unwrap();
+        match templates {
+            Ok(templates) => {
+                event_log.push(LogEntry {
+                    message: "Reloaded template.ron".to_string(),
+                    color: ColorPair::new(GREEN, BLACK),
+                });
+                drop(event_log);
+                self.resources.insert(templates);
+            }
+            Err(problems) => problems.into_iter().for_each(|message| {
+                event_log.push(LogEntry {
+                    message,
+                    color: ColorPair::new(RED, BLACK),
+                })
+            }),
+        }
+    }
+
+    // Replaces every monster, item and hazard lying around on this level with a fresh one from the current templates,
+    // right where the old one was
+    pub fn respawn_level_entities(&mut self) {
+        let templates = self.resources.get::<Templates>().unwrap();
+        let map_level = <&Player>::query()
+            .iter(&self.ecs)
+            .map(|player| player.map_level)
+            .next()
+            .unwrap();
+
+        let mut command_buffer = CommandBuffer::new(&self.ecs);
+        let mut respawns = Vec::new();
+        <(Entity, &Point, &Name)>::query()
+            .filter(component::<Enemy>() | component::<Item>() | component::<Hazard>())
+            .iter(&self.ecs)
+            .filter(|(_, _, name)| templates.has(&name.0))
+            .for_each(|(entity, pos, name)| {
+                command_buffer.remove(*entity);
+                respawns.push((*pos, name.0.clone()));
+            });
+        command_buffer.flush(&mut self.ecs);
+        templates.spawn_named(&mut self.ecs, map_level as usize, &respawns);
+    }
+
+    // Takes aim with the drone's ranged weapon, or with the item it is about to use
+    // Firing needs something that can fire right now, an item can go anywhere the drone sees
+    pub fn start_targeting(&mut self, aiming: Aiming) {
+        let player = self.player();
+        let range = match aiming {
+            Aiming::Fire => firearm(&self.ecs, player).map(|(_, ranged)| ranged.range),
+            Aiming::Item(..) => Ok(self
+                .ecs
+                .entry_ref(player)
+                .ok()
+                .and_then(|entry| entry.get_component::<FieldOfView>().ok().map(|f| f.radius))
+                .unwrap_or(0)),
+        };
+        match range {
+            Ok(range) => {
+                let targeting = Targeting::new(&self.ecs, player, range, aiming);
+                self.resources.insert(Some(targeting));
+                self.resources.insert(TurnState::Targeting);
+            }
+            Err(reason) => self
+                .resources
+                .get_mut::<EventLog>()
+                .unwrap()
+                .push(LogEntry {
+                    message: reason,
+                    color: ColorPair::new(WHITE, BLACK),
+                }),
+        }
+    }
+
+    // Radius of the area the nth item in the inventory hits, None when it is used without picking a tile
+    pub fn item_area(&self, n: usize) -> Option<i32> {
+        item_area(&self.ecs, self.player(), n)
+    }
+
+    // Puts the weapon or item down again without using it
+    pub fn stop_targeting(&mut self) {
+        self.resources.insert::<Option<Targeting>>(None);
+        self.resources.insert(TurnState::AwaitingInput);
+    }
+
+    // Spends one level on whatever the player picked, it goes into the replay like any other action
+    fn level_up(&mut self, choice: LevelUpChoice) {
+        let player = self.player();
+        let mut entry = self.ecs.entry(player).unwrap();
+
+        let progress = entry.get_component_mut::<Progress>().unwrap();
+        progress.xp -= progress.xp_to_next_level();
+        progress.level += 1;
+        let level = progress.level;
+        let can_level_up = progress.can_level_up();
+
+        let improvement = match choice {
+            LevelUpChoice::Health => {
+                let health = entry.get_component_mut::<Health>().unwrap();
+                health.max += 5;
+                health.current += 5;
+                "a sturdier hull"
+            }
+            LevelUpChoice::Damage => {
+                entry.get_component_mut::<Damage>().unwrap().0.bonus += 1;
+                "sharper rotors"
+            }
+            LevelUpChoice::Sight => {
+                let fov = entry.get_component_mut::<FieldOfView>().unwrap();
+                fov.radius += 2;
+                fov.is_dirty = true;
+                "better optics"
+            }
+        };
+
+        self.resources
+            .get_mut::<EventLog>()
+            .unwrap()
+            .push(LogEntry {
+                message: format!("Reached level {}, {}", level, improvement),
+                color: ColorPair::new(YELLOW, BLACK),
+            });
+        self.resources
+            .get_mut::<Replay>()
+            .unwrap()
+            .actions
+            .push(PlayerAction::LevelUp(choice));
+        // Enough experience for more than one level, the screen stays up for the next one
+        self.resources.insert(if can_level_up {
+            TurnState::LevelUp
+        } else {
+            TurnState::AwaitingInput
+        });
+    }
+
+    fn player(&self) -> Entity {
+        <Entity>::query()
+            .filter(component::<Player>())
+            .iter(&self.ecs)
+            .copied()
+            .next()
+            .unwrap()
+    }
+
+    // Leaves the current level behind, exactly as it is, and takes the player one level down (offset 1)
+    // or back up (offset -1). Levels we've been on before are picked up again, new ones are built
+    fn change_level(&mut self, offset: i32) {
+        // Get the player entity id, the level we are leaving and the stairs we are leaving it by
+        let (player_entity, old_level, left_from) = <(Entity, &Player, &Point)>::query()
+            .iter(&self.ecs)
+            .map(|(entity, player, pos)| (*entity, player.map_level, *pos))
+            .next()
+            .unwrap();
+        let new_level = (old_level as i32 + offset) as u32;
+
+        // A much more effiecent way of performing multiple commands to the ECS system
+        // is through this method
+        let mut command_buffer = CommandBuffer::new(&self.ecs);
+        // Whatever isn't on a level and isn't carried by anyone (messages mostly) has no reason to stay
+        Entity::query()
+            .filter(
+                !component::<Point>()
+                    & !component::<Carried>()
+                    & !component::<OtherLevelPosition>(),
+            )
+            .iter(&self.ecs)
+            .for_each(|e| command_buffer.remove(*e));
+        // Everything else on this level stays behind, swapping its Point out keeps it out of our systems
+        // The player's carried items have no Point so they come along
+        <(Entity, &Point)>::query()
+            .filter(!component::<Player>())
+            .iter(&self.ecs)
+            .for_each(|(e, pos)| {
+                command_buffer.remove_component::<Point>(*e);
+                command_buffer.add_component(
+                    *e,
+                    OtherLevelPosition {
+                        pos: *pos,
+                        map_level: old_level,
+                    },
+                );
+            });
+
+        // Store the map we are leaving and see if we already know the one we're heading to
+        let map = self.resources.remove::<Map>().unwrap();
+        let theme = self.resources.remove::<Box<dyn MapTheme>>().unwrap();
+        let mut visited_levels = self.resources.get_mut::<VisitedLevels>().unwrap();
+        visited_levels.store(old_level, map, theme.as_ref(), left_from);
+        let stored_level =
+            visited_levels.take(new_level, &self.resources.get::<ThemeLibrary>().unwrap());
+        drop(visited_levels);
+
+        let (map, theme, arrival) = match stored_level {
+            Some((map, theme, left_from)) => {
+                // Bring back everything we left on that level
+                <(Entity, &OtherLevelPosition)>::query()
+                    .iter(&self.ecs)
+                    .filter(|(_, other)| other.map_level == new_level)
+                    .for_each(|(e, other)| {
+                        command_buffer.remove_component::<OtherLevelPosition>(*e);
+                        command_buffer.add_component(*e, other.pos);
+                    });
+                command_buffer.flush(&mut self.ecs);
+
+                // We arrive by the stairs we left the level by, older saves didn't keep them so look for them:
+                // going down we arrive by the up staircase, going up we arrive by the exit we took
+                let stairs = if offset > 0 {
+                    TileType::StairsUp
+                } else {
+                    TileType::Exit
+                };
+                let arrival = left_from
+                    .or_else(|| {
+                        map.tiles
+                            .iter()
+                            .position(|t| *t == stairs)
+                            .map(|idx| map.index_to_point2d(idx))
+                    })
+                    .map(|stairs| map.arrival_point(stairs))
+                    // Something wrote over the stairs, anywhere to stand will do
+                    .or_else(|| {
+                        (0..map.tiles.len())
+                            .map(|idx| map.index_to_point2d(idx))
+                            .find(|pt| map.can_enter_tile(*pt))
+                    })
+                    .unwrap_or_else(Point::zero);
+                (map, theme, arrival)
+            }
+            None => {
+                command_buffer.flush(&mut self.ecs);
+
+                // Create a map just like we've done in other functions before
+                // Borrow the run's generator so the new level follows from the seed as well
+                let mut rng = self.resources.get_mut::<RandomNumberGenerator>().unwrap();
+                let campaign = self.resources.get::<Campaign>().unwrap();
+                let templates = self.resources.get::<Templates>().unwrap();
+                let vaults = self.resources.get::<VaultLibrary>().unwrap();
+                let themes = self.resources.get::<ThemeLibrary>().unwrap();
+                let level = campaign.level(new_level);
+                let mut mb = MapBuilder::new(
+                    &mut rng, new_level, level, &vaults, &themes, MAP_WIDTH, MAP_HEIGHT,
+                );
+
+                // The way back up is where the player starts
+                let stairs_idx = mb.map.point2d_to_index(mb.player_start);
+                mb.map.tiles[stairs_idx] = TileType::StairsUp;
+
+                // Decide on wheter we spawn staircase or teleportation crystal
+                if level.teleportation_crystal {
+                    spawn_telerportation_crystal(&mut self.ecs, mb.teleportation_crystal_start);
+                } else {
+                    let exit_idx = mb.map.point2d_to_index(mb.teleportation_crystal_start);
+                    mb.map.tiles[exit_idx] = TileType::Exit;
+                }
+                spawn_level(
+                    &mut self.ecs,
+                    &templates,
+                    &mut rng,
+                    new_level as usize,
+                    &mb.monster_spawns,
+                    &mb.guaranteed_monster_spawns,
+                    &mb.template_spawns,
+                );
+
+                // Only the first visit to a level hands out more waiting turns
+                if let Ok(mut entry) = self.ecs.entry_mut(player_entity) {
+                    entry.get_component_mut::<Player>().unwrap().wait_count += 4;
+                }
+
+                let arrival = mb.map.arrival_point(mb.player_start);
+                (mb.map, mb.theme, arrival)
+            }
+        };
+
+        // Move the player over to the new level
+        <(&mut Player, &mut Point)>::query()
+            .iter_mut(&mut self.ecs)
+            .for_each(|(player, pos)| {
+                player.map_level = new_level;
+                *pos = arrival;
+            });
+
+        // Mark field of view as dirty
+        // Making it not retain to the next level
+        // Notice the iter_mut(...) here since we modify the fov component
+        <&mut FieldOfView>::query()
+            .iter_mut(&mut self.ecs)
+            .for_each(|fov| fov.is_dirty = true);
+
+        // Finally add our ECS resources as always
+        self.resources.insert(map);
+        self.resources.insert(Camera::new(arrival));
+        self.resources.insert(TurnState::AwaitingInput);
+        self.resources.insert(theme);
+        self.resources.insert(EventLog::new());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Wanders around, picks up whatever it steps on and puts levels into health, the same way for every run
+    fn play(sim: &mut Simulation, turns: usize) {
+        let mut rng = RandomNumberGenerator::seeded(7);
+        for _ in 0..turns {
+            let action = match sim.turn_state() {
+                TurnState::LevelUp => PlayerAction::LevelUp(LevelUpChoice::Health),
+                TurnState::AwaitingInput => match rng.range(0, 6) {
+                    0 => PlayerAction::Wait,
+                    1 => PlayerAction::PickUp,
+                    _ => PlayerAction::Move(random_direction(&mut rng)),
+                },
+                _ => return,
+            };
+            sim.play_turn(action);
+        }
+    }
+
+    // Replays the actions a run recorded on a fresh simulation from the same seed, set up like the run was
+    fn replay(sim: &Simulation, setup: impl Fn(&mut Simulation)) -> Simulation {
+        let recorded = sim.resources.get::<Replay>().unwrap().clone();
+        let mut replayed = Simulation::new(recorded.seed);
+        setup(&mut replayed);
+        recorded.actions.iter().for_each(|action| {
+            replayed.play_turn(*action);
+        });
+        replayed
+    }
+
+    // Everything that tells two runs apart: the map, where everyone is and how they are doing
+    fn snapshot(sim: &Simulation) -> (Vec<TileType>, Vec<String>) {
+        let mut entities: Vec<String> = <(&Name, Option<&Point>, Option<&Health>)>::query()
+            .iter(&sim.ecs)
+            .map(|(name, pos, health)| format!("{} {:?} {:?}", name.0, pos, health))
+            .collect();
+        entities.sort();
+        (sim.resources.get::<Map>().unwrap().tiles.clone(), entities)
+    }
+
+    fn give_status(sim: &mut Simulation, entity: Entity, kind: StatusKind, turns: i32) {
+        let mut entry = sim.ecs.entry(entity).unwrap();
+        entry
+            .get_component_mut::<Statuses>()
+            .unwrap()
+            .apply(kind, turns);
+    }
+
+    fn confuse_player(sim: &mut Simulation) {
+        let player = sim.player();
+        give_status(sim, player, StatusKind::Confused, 1000);
+    }
+
+    // Waiting only passes the turn while the drone has waiting turns left
+    fn let_player_wait(sim: &mut Simulation) {
+        <&mut Player>::query()
+            .iter_mut(&mut sim.ecs)
+            .for_each(|player| player.wait_count = 100);
+    }
+
+    #[test]
+    fn status_messages_show_on_the_turn_they_happen() {
+        let mut sim = Simulation::new(RunSeed(42));
+        let player = sim.player();
+        let_player_wait(&mut sim);
+        give_status(&mut sim, player, StatusKind::Poisoned, 1);
+        sim.play_turn(PlayerAction::Wait);
+
+        let log = sim.resources.get::<EventLog>().unwrap().clone();
+        assert!(
+            log.messages
+                .iter()
+                .any(|entry| entry.message.contains("no longer poisoned")),
+            "{:?}",
+            log.messages
+        );
+    }
+
+    #[test]
+    fn hasted_player_acts_twice_for_every_monster_turn() {
+        let mut sim = Simulation::new(RunSeed(42));
+        let player = sim.player();
+        let_player_wait(&mut sim);
+        give_status(&mut sim, player, StatusKind::Hasted, 10);
+        for _ in 0..4 {
+            sim.play_turn(PlayerAction::Wait);
+        }
+
+        // Statuses count down on monster turns, four player turns only gave the monsters two
+        let entry = sim.ecs.entry_ref(player).unwrap();
+        let haste = entry
+            .get_component::<Statuses>()
+            .unwrap()
+            .get(StatusKind::Hasted)
+            .copied();
+        assert_eq!(haste.map(|haste| haste.turns), Some(8));
+    }
+
+    #[test]
+    fn hazards_land_on_the_turn_they_are_stepped_on() {
+        let mut sim = Simulation::new(RunSeed(42));
+        let_player_wait(&mut sim);
+        // A stunned monster stays put on the hazard for its whole turn
+        let (monster, pos) = <(Entity, &Point)>::query()
+            .filter(component::<Enemy>())
+            .iter(&sim.ecs)
+            .map(|(entity, pos)| (*entity, *pos))
+            .next()
+            .unwrap();
+        give_status(&mut sim, monster, StatusKind::Stunned, 10);
+        let templates = sim.resources.get::<Templates>().unwrap().clone();
+        templates.spawn_named(&mut sim.ecs, 0, &[(pos, "Spore Patch".to_string())]);
+        sim.play_turn(PlayerAction::Wait);
+
+        let entry = sim.ecs.entry_ref(monster).unwrap();
+        let statuses = entry.get_component::<Statuses>().unwrap();
+        assert!(statuses.has(StatusKind::Poisoned));
+        // Nothing is left waiting for the next turn, where a save would lose it
+        assert_eq!(<&ApplyEffect>::query().iter(&sim.ecs).count(), 0);
+    }
+
+    #[test]
+    fn area_items_need_a_tile() {
+        let mut sim = Simulation::new(RunSeed(42));
+        let player = sim.player();
+        let pos = *sim
+            .ecs
+            .entry_ref(player)
+            .unwrap()
+            .get_component::<Point>()
+            .unwrap();
+        let templates = sim.resources.get::<Templates>().unwrap().clone();
+        templates.spawn_named(&mut sim.ecs, 0, &[(pos, "Fire Flask".to_string())]);
+        sim.play_turn(PlayerAction::PickUp);
+        let flask = carried_items(&sim.ecs, player)
+            .iter()
+            .position(|item| {
+                let entry = sim.ecs.entry_ref(*item).unwrap();
+                entry.get_component::<Name>().unwrap().0 == "Fire Flask"
+            })
+            .unwrap();
+        let carried = carried_items(&sim.ecs, player).len();
+
+        // Without a tile the flask stays in the inventory
+        sim.play_turn(PlayerAction::UseItem(flask));
+        assert_eq!(carried_items(&sim.ecs, player).len(), carried);
+
+        sim.play_turn(PlayerAction::UseItemAt(flask, pos));
+        assert_eq!(carried_items(&sim.ecs, player).len(), carried - 1);
+    }
+
+    #[test]
+    fn same_seed_and_actions_give_the_same_run() {
+        let mut first = Simulation::new(RunSeed(42));
+        let mut second = Simulation::new(RunSeed(42));
+        play(&mut first, 200);
+        play(&mut second, 200);
+        assert_eq!(snapshot(&first), snapshot(&second));
+    }
+
+    #[test]
+    fn replay_reproduces_the_run() {
+        let mut sim = Simulation::new(RunSeed(42));
+        play(&mut sim, 200);
+        assert_eq!(snapshot(&replay(&sim, |_| {})), snapshot(&sim));
+    }
+
+    #[test]
+    fn replay_reproduces_a_confused_run() {
+        // A confused drone rolls for where it goes, even when that is into a wall
+        let mut sim = Simulation::new(RunSeed(42));
+        confuse_player(&mut sim);
+        play(&mut sim, 200);
+        assert_eq!(snapshot(&replay(&sim, confuse_player)), snapshot(&sim));
+    }
+}
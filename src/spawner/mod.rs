@@ -1,18 +1,19 @@
 // Handles spawning entities
 
 use crate::prelude::*;
-use template::Templates;
+pub use template::Templates;
 
 mod template;
 
 pub fn spawn_level(
     ecs: &mut World,
+    template: &Templates,
     rng: &mut RandomNumberGenerator,
     level: usize,
     spawn_points: &[Point],
     guaranteed_monster_spawn_points: &[Point],
+    template_spawns: &[(Point, String)],
 ) {
-    let template = Templates::load();
     template.spawn_entities(
         ecs,
         rng,
@@ -20,11 +21,12 @@ pub fn spawn_level(
         spawn_points,
         guaranteed_monster_spawn_points,
     );
+    template.spawn_named(ecs, level, template_spawns);
 }
 
 pub fn spawn_player(ecs: &mut World, pos: Point) {
     // Pushes a new player with Player, Point and Render components
-    ecs.push((
+    let player = ecs.push((
         Player {
             map_level: 0,
             wait_count: 8,
@@ -39,9 +41,14 @@ pub fn spawn_player(ecs: &mut World, pos: Point) {
             max: 20,
         },
         FieldOfView::new(8),
-        Damage(1),
+        Damage(DiceType::new(1, 2, 0)),
         Name("Drone".to_string()),
+        Statuses::default(),
     ));
+    // Legion only takes eight components at a time, the rest are added afterwards
+    if let Some(mut entry) = ecs.entry(player) {
+        entry.add_component(Progress::new());
+    }
 }
 
 pub fn spawn_telerportation_crystal(ecs: &mut World, pos: Point) {
@@ -1,15 +1,29 @@
 use crate::prelude::*;
 use legion::systems::CommandBuffer;
-use ron::de::from_reader;
+use ron::de::from_str;
 use serde::Deserialize;
 use std::collections::HashSet;
-use std::fs::File;
 
 #[derive(Clone, Deserialize, Debug, PartialEq)]
 pub enum EntityType {
     Enemy,
     EnemyEveryOther,
     Item,
+    Hazard, // Sits on its tile and sets off its provides on whoever stands there
+    Armour, // An item that is worn for its defence
+}
+
+impl EntityType {
+    // What a template gets drawn in when it doesn't pick a color of its own, (r, g, b) like the themes
+    fn color(&self) -> (u8, u8, u8) {
+        match self {
+            EntityType::Enemy => (255, 95, 75),
+            EntityType::EnemyEveryOther => (235, 140, 60),
+            EntityType::Item => (120, 200, 255),
+            EntityType::Armour => (190, 195, 215),
+            EntityType::Hazard => (175, 230, 80),
+        }
+    }
 }
 
 // This struct is reflected in the template.ron
@@ -23,12 +37,29 @@ pub struct Template {
     pub glyph: char,
     pub levels: HashSet<usize>,
     pub frequency: i32,
-    pub provides: Option<Vec<(String, i32)>>,
+    pub provides: Option<Vec<Effect>>,
     pub hp: Option<i32>,
-    pub base_damage: Option<i32>,
+    // Dice notation such as "1d4+1", or a flat number
+    pub damage: Option<String>,
+    pub accuracy: Option<i32>,
+    pub evasion: Option<i32>,
+    // Damage taken off every hit, for monsters and armour
+    pub defence: Option<i32>,
+    // Experience the drone gets for killing it
+    pub xp: Option<i32>,
+    // Effects that come with every hit, for monsters and weapons
+    pub on_hit: Option<Vec<Effect>>,
+    // Monsters and weapons with a range shoot from afar, at most once every cooldown turns
+    pub range: Option<i32>,
+    pub cooldown: Option<i32>,
+    // Shots a ranged weapon has in it, as many as you like when there is no ammo
+    pub ammo: Option<i32>,
+    // (r, g, b) it is drawn in, otherwise the color that goes with its entity_type
+    pub color: Option<(u8, u8, u8)>,
 }
 
-#[derive(Clone, Deserialize, Debug)]
+// Parsed once and kept as a resource, an empty set of templates spawns nothing
+#[derive(Clone, Default, Deserialize, Debug)]
 pub struct Templates {
     pub entities: Vec<Template>,
 }
@@ -36,10 +67,139 @@ pub struct Templates {
 // Notice that Templates is Vec of Template and check the load() function
 // from_reader is from RON and translates using serde somehow into the Templates struct, very nice
 impl Templates {
-    pub fn load() -> Self {
-        let file =
-            File::open("resources/template.ron").expect("Failed opening template.ron file!!!");
-        from_reader(file).expect("Unable to load templates")
+    // Either the templates or every problem we found with them, one line per problem
+    pub fn load() -> Result<Self, Vec<String>> {
+        Self::parse(&data_file("template.ron", TEMPLATE_DATA))
+    }
+
+    // Same as load but from the contents of a template.ron we've already read
+    pub fn parse(contents: &str) -> Result<Self, Vec<String>> {
+        let templates: Self =
+            from_str(contents).map_err(|e| vec![format!("Unable to parse template.ron: {}", e)])?;
+        let problems = templates.validate();
+        if problems.is_empty() {
+            Ok(templates)
+        } else {
+            Err(problems)
+        }
+    }
+
+    // Is there a template going by this name?
+    pub fn has(&self, name: &str) -> bool {
+        self.entities.iter().any(|t| t.name == name)
+    }
+
+    // Looks through all of the templates instead of stopping at the first problem, so they can all be fixed in one go
+    fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (i, template) in self.entities.iter().enumerate() {
+            let name = &template.name;
+            let is_enemy = matches!(
+                template.entity_type,
+                EntityType::Enemy | EntityType::EnemyEveryOther
+            );
+            if is_enemy && template.hp.is_none() {
+                problems.push(format!("{}: enemies need an hp", name));
+            }
+            if template.entity_type == EntityType::Hazard && template.provides.is_none() {
+                problems.push(format!("{}: hazards need something to provide", name));
+            }
+            match (template.defence, &template.entity_type) {
+                (None, EntityType::Armour) => {
+                    problems.push(format!("{}: armour needs a defence", name))
+                }
+                (Some(defence), _) if defence < 1 => {
+                    problems.push(format!("{}: defence has to be at least 1", name))
+                }
+                (Some(_), EntityType::Item | EntityType::Hazard) => {
+                    problems.push(format!("{}: only enemies and armour have a defence", name))
+                }
+                _ => {}
+            }
+            if let Some(damage) = &template.damage {
+                if parse_damage(damage).is_none() {
+                    problems.push(format!("{}: can't make out the damage {}", name, damage));
+                }
+                // Armour is worn, not swung, so it would never get to use the damage
+                if template.entity_type == EntityType::Armour {
+                    problems.push(format!("{}: armour doesn't deal damage", name));
+                }
+            }
+            match template.xp {
+                Some(_) if !is_enemy => {
+                    problems.push(format!("{}: only enemies give any xp", name))
+                }
+                Some(xp) if xp < 1 => problems.push(format!("{}: xp has to be at least 1", name)),
+                _ => {}
+            }
+            if template.on_hit.is_some() && !is_enemy && template.damage.is_none() {
+                problems.push(format!("{}: only enemies and weapons hit anything", name));
+            }
+            match template.range {
+                Some(_) if !is_enemy && template.damage.is_none() => {
+                    problems.push(format!("{}: only enemies and weapons shoot", name))
+                }
+                Some(range) if range < 2 => {
+                    problems.push(format!("{}: range has to be at least 2", name))
+                }
+                None if template.cooldown.is_some() || template.ammo.is_some() => problems.push(
+                    format!("{}: cooldown and ammo only make sense with a range", name),
+                ),
+                _ => {}
+            }
+            if template.cooldown.is_some_and(|cooldown| cooldown < 1) {
+                problems.push(format!("{}: cooldown has to be at least 1", name));
+            }
+            match template.ammo {
+                Some(_) if is_enemy => {
+                    problems.push(format!("{}: enemies never run out of ammo", name))
+                }
+                Some(ammo) if ammo < 1 => {
+                    problems.push(format!("{}: ammo has to be at least 1", name))
+                }
+                _ => {}
+            }
+            if template.levels.is_empty() {
+                problems.push(format!("{}: levels is empty, it would never spawn", name));
+            }
+            if template.frequency < 1 {
+                problems.push(format!("{}: frequency has to be at least 1", name));
+            }
+            let provides = template.provides.iter().flatten();
+            if provides
+                .clone()
+                .any(|effect| effect.target() == Target::Victim)
+            {
+                problems.push(format!("{}: only on_hit effects have a Victim", name));
+            }
+            // Only someone using an item gets to pick the tile first
+            let area_elsewhere = match template.entity_type {
+                EntityType::Item => template.on_hit.iter().flatten().any(|e| e.area().is_some()),
+                _ => provides
+                    .clone()
+                    .chain(template.on_hit.iter().flatten())
+                    .any(|e| e.area().is_some()),
+            };
+            if area_elsewhere {
+                problems.push(format!("{}: only items can have an Area target", name));
+            }
+            provides
+                .chain(template.on_hit.iter().flatten())
+                .filter_map(|effect| effect.problem())
+                .for_each(|problem| problems.push(format!("{}: {}", name, problem)));
+            // Sharing a name is fine (a stronger potion further down), as long as the levels don't overlap
+            // otherwise there is no telling which one a vault asking for the name should get
+            let duplicate = self.entities[..i]
+                .iter()
+                .any(|other| other.name == *name && !other.levels.is_disjoint(&template.levels));
+            if duplicate {
+                problems.push(format!(
+                    "{}: another template with this name shares some of its levels",
+                    name
+                ));
+            }
+        }
+        problems
     }
 
     pub fn spawn_entities(
@@ -88,13 +248,38 @@ impl Templates {
         command_buffer.flush(ecs);
     }
 
+    // Spawns exactly the templates asked for, used by the vaults to put things at a specific spot
+    // Templates can share a name across levels, the one meant for this level wins
+    pub fn spawn_named(&self, ecs: &mut World, level: usize, spawns: &[(Point, String)]) {
+        let mut command_buffer = CommandBuffer::new(ecs);
+        spawns.iter().for_each(|(point, name)| {
+            let mut named = self.entities.iter().filter(|e| e.name == *name);
+            let template = named
+                .clone()
+                .find(|e| e.levels.contains(&level))
+                .or_else(|| named.next());
+            match template {
+                Some(template) => self.spawn_entity(point, template, &mut command_buffer),
+                // The vaults are checked against the templates when they load, so this means a bug
+                None => EventLog::log(
+                    &mut command_buffer,
+                    format!("There is no template called {}", name),
+                ),
+            }
+        });
+        command_buffer.flush(ecs);
+    }
+
     fn spawn_entity(&self, pt: &Point, template: &Template, commands: &mut CommandBuffer) {
         // Entities share a base-set of components that make them viable in the world
         // add those then match type to add specifics
+        let color = template
+            .color
+            .unwrap_or_else(|| template.entity_type.color());
         let entity = commands.push((
             pt.clone(),
             Render {
-                color: ColorPair::new(WHITE, BLACK),
+                color: ColorPair::new(RGB::named(color), BLACK),
                 glyph: to_cp437(template.glyph),
             },
             Name(template.name.clone()),
@@ -103,15 +288,26 @@ impl Templates {
         // Now match type and add the correct bag of components
         match template.entity_type {
             EntityType::Item => commands.add_component(entity, Item {}),
+            EntityType::Hazard => commands.add_component(entity, Hazard {}),
+            EntityType::Armour => {
+                commands.add_component(entity, Item {});
+                commands.add_component(entity, Armour {});
+            }
             EntityType::Enemy | EntityType::EnemyEveryOther => {
                 commands.add_component(entity, Enemy {});
                 commands.add_component(entity, FieldOfView::new(6));
                 commands.add_component(entity, ChasingPlayer);
+                commands.add_component(entity, Statuses::default());
+                if let Some(xp) = template.xp {
+                    commands.add_component(entity, GrantsXp(xp));
+                }
+                // validate makes sure every enemy has its hp
+                let hp = template.hp.unwrap_or(1);
                 commands.add_component(
                     entity,
                     Health {
-                        current: template.hp.unwrap(),
-                        max: template.hp.unwrap(),
+                        current: hp,
+                        max: hp,
                     },
                 );
                 // If it is type Enemy_Every_Other also add that component
@@ -121,25 +317,53 @@ impl Templates {
             }
         }
 
-        // Item provides? Add correct component
+        // Item provides? Using it sets off every one of its effects
         if let Some(effects) = &template.provides {
-            effects
-                .iter()
-                .for_each(|(provides, n)| match provides.as_str() {
-                    "Healing" => commands.add_component(entity, ProvidesHealing { amount: *n }),
-                    "MagicMap" => commands.add_component(entity, ProvidesDungeonMap {}),
-                    "GroundStomp" => commands.add_component(entity, ProvidesStun {}),
-                    _ => {
-                        println!("we don't know how to provide {}", provides)
-                    }
-                });
+            commands.add_component(entity, Provides(effects.clone()));
+        }
+
+        if let Some(defence) = template.defence {
+            commands.add_component(entity, Defence(defence));
         }
 
-        if let Some(damage) = &template.base_damage {
-            commands.add_component(entity, Damage(*damage));
+        if let Some(effects) = &template.on_hit {
+            commands.add_component(entity, OnHit(effects.clone()));
+        }
+
+        if let Some(accuracy) = template.accuracy {
+            commands.add_component(entity, Accuracy(accuracy));
+        }
+        if let Some(evasion) = template.evasion {
+            commands.add_component(entity, Evasion(evasion));
+        }
+
+        if let Some(range) = template.range {
+            commands.add_component(
+                entity,
+                Ranged {
+                    range,
+                    cooldown: template.cooldown.unwrap_or(0),
+                },
+            );
+        }
+        if let Some(ammo) = template.ammo {
+            commands.add_component(entity, Ammo(ammo));
+        }
+
+        // validate makes sure the damage can be read
+        if let Some(damage) = template.damage.as_deref().and_then(parse_damage) {
+            commands.add_component(entity, Damage(damage));
             if template.entity_type == EntityType::Item {
                 commands.add_component(entity, Weapon {});
             }
         }
     }
 }
+
+// Damage is written in dice notation ("1d4+1") or as a flat number ("2")
+fn parse_damage(damage: &str) -> Option<DiceType> {
+    match damage.trim().parse::<i32>() {
+        Ok(flat) => Some(DiceType::new(0, 0, flat)),
+        Err(_) => parse_dice_string(damage).ok(),
+    }
+}
@@ -0,0 +1,160 @@
+use crate::prelude::*;
+
+// Every effect goes through here, whether it came from an item or something else that sends ApplyEffect
+#[system]
+#[read_component(ApplyEffect)]
+#[read_component(Point)]
+#[read_component(Player)]
+#[read_component(Enemy)]
+#[write_component(Health)]
+#[write_component(Statuses)]
+#[read_component(FieldOfView)]
+#[read_component(GrantsXp)]
+#[write_component(Progress)]
+#[read_component(Name)]
+pub fn apply_effects(ecs: &mut SubWorld, commands: &mut CommandBuffer, #[resource] map: &mut Map) {
+    let messages: Vec<(Entity, ApplyEffect)> = <(Entity, &ApplyEffect)>::query()
+        .iter(ecs)
+        .map(|(entity, apply)| (*entity, *apply))
+        .collect();
+
+    messages.iter().for_each(|(message, apply)| {
+        let targets = targets(ecs, apply, apply.effect.target());
+        match apply.effect {
+            Effect::Heal { amount, .. } => targets.iter().for_each(|target| {
+                if let Ok(health) = ecs
+                    .entry_mut(*target)
+                    .unwrap()
+                    .get_component_mut::<Health>()
+                {
+                    health.current = i32::min(health.max, health.current + amount);
+                }
+                if let Ok(name) = ecs.entry_ref(*target).unwrap().get_component::<Name>() {
+                    EventLog::log(commands, format!("{} healed for {}hp", name.0, amount));
+                }
+            }),
+            Effect::Damage { amount, .. } => targets.iter().for_each(|target| {
+                let is_player = ecs
+                    .entry_ref(*target)
+                    .unwrap()
+                    .get_component::<Player>()
+                    .is_ok();
+                let mut killed = false;
+                if let Ok(health) = ecs
+                    .entry_mut(*target)
+                    .unwrap()
+                    .get_component_mut::<Health>()
+                {
+                    health.current -= amount;
+                    // Same as in combat, end_turn takes care of the player running out of health
+                    killed = health.current < 1 && !is_player;
+                }
+                if killed {
+                    commands.remove(*target);
+                    grant_xp(ecs, commands, apply.user, *target);
+                }
+                if let Ok(name) = ecs.entry_ref(*target).unwrap().get_component::<Name>() {
+                    EventLog::log(commands, format!("{} took {} damage", name.0, amount));
+                }
+            }),
+            Effect::Status {
+                status, duration, ..
+            } => {
+                targets.iter().for_each(|target| {
+                    let mut entry = ecs.entry_mut(*target).unwrap();
+                    if let Ok(statuses) = entry.get_component_mut::<Statuses>() {
+                        statuses.apply(status, duration);
+                    }
+                    // Blindness closes in on the field of view right away
+                    if status == StatusKind::Blinded {
+                        if let Ok(fov) = entry.get_component::<FieldOfView>() {
+                            commands.add_component(*target, fov.clone_dirty());
+                        }
+                    }
+                });
+                if !targets.is_empty() {
+                    EventLog::log(
+                        commands,
+                        format!(
+                            "{} {} for {} turns",
+                            who(ecs, &targets),
+                            status.name(),
+                            duration
+                        ),
+                    );
+                }
+            }
+            Effect::RevealMap => {
+                map.revealed_tiles.iter_mut().for_each(|t| *t = true);
+                EventLog::log(commands, "The Magic Map reveals".to_string());
+            }
+        }
+        commands.remove(*message);
+    });
+}
+
+// Everyone the effect lands on, only those with health since there is nothing to heal, hurt or stun on the rest
+fn targets(ecs: &SubWorld, apply: &ApplyEffect, target: Target) -> Vec<Entity> {
+    let user = apply.user;
+    let center = match ecs
+        .entry_ref(user)
+        .map(|u| u.get_component::<Point>().copied())
+    {
+        Ok(Ok(pos)) => pos,
+        // The user is gone or not on this level, there is nobody around it
+        _ => return Vec::new(),
+    };
+    match target {
+        Target::User => vec![user],
+        // The hit that sent it may have killed the victim already
+        Target::Victim => apply
+            .victim
+            .filter(|victim| ecs.entry_ref(*victim).is_ok())
+            .into_iter()
+            .collect(),
+        Target::Around { .. } | Target::Area { .. } => {
+            let (center, radius, kind) = match (target, apply.target) {
+                (Target::Around { radius, kind }, _) => (center, radius, kind),
+                (Target::Area { radius, kind }, Some(picked)) => (picked, radius, kind),
+                // Nobody picked a tile for it, so there is nobody there
+                _ => return Vec::new(),
+            };
+            let user_is_player = ecs
+                .entry_ref(user)
+                .unwrap()
+                .get_component::<Player>()
+                .is_ok();
+            <(Entity, &Point)>::query()
+                .filter(component::<Health>())
+                .iter(ecs)
+                // Around never hits the user, an Area can when everyone is in for it
+                .filter(|(entity, _)| **entity != user || matches!(target, Target::Area { .. }))
+                .filter(|(_, pos)| {
+                    DistanceAlg::Pythagoras.distance2d(center, **pos) <= radius as f32
+                })
+                .filter(|(entity, _)| {
+                    let entry = ecs.entry_ref(**entity).unwrap();
+                    match kind {
+                        TargetKind::Everyone => true,
+                        TargetKind::Foes if user_is_player => {
+                            entry.get_component::<Enemy>().is_ok()
+                        }
+                        TargetKind::Foes => entry.get_component::<Player>().is_ok(),
+                    }
+                })
+                .map(|(entity, _)| *entity)
+                .collect()
+        }
+    }
+}
+
+// Name of the one target, or just how many there are
+fn who(ecs: &SubWorld, targets: &[Entity]) -> String {
+    match targets {
+        [target] => match ecs.entry_ref(*target).unwrap().get_component::<Name>() {
+            Ok(name) => name.0.clone(),
+            Err(_) => "One creature".to_string(),
+        },
+        _ => format!("{} creatures", targets.len()),
+    }
+}
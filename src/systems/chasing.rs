@@ -1,15 +1,29 @@
 use crate::prelude::*;
 
+// How close a ranged monster lets the player get before it backs off
+const KEEP_DISTANCE: f32 = 3.0;
+
 #[system]
 #[read_component(Point)]
 #[read_component(ChasingPlayer)]
 #[read_component(Health)]
 #[read_component(Player)]
 #[read_component(FieldOfView)]
+#[read_component(Statuses)]
 #[write_component(MoveEveryOther)]
-pub fn chasing(#[resource] map: &Map, ecs: &mut SubWorld, commands: &mut CommandBuffer) {
-    // Go through each MoveEveryOther entity and toggle their value
-    let mut every_other_movers = <&mut MoveEveryOther>::query();
+#[read_component(Ranged)]
+#[read_component(Cooldown)]
+#[read_component(Carried)]
+#[read_component(Ammo)]
+#[read_component(Name)]
+pub fn chasing(
+    #[resource] map: &Map,
+    #[resource] rng: &mut RandomNumberGenerator,
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+) {
+    // Go through each MoveEveryOther entity on this level and toggle their value
+    let mut every_other_movers = <&mut MoveEveryOther>::query().filter(component::<Point>());
     every_other_movers.iter_mut(ecs).for_each(|met| {
         met.0 = !met.0; // Flip the bool
     });
@@ -17,47 +31,145 @@ pub fn chasing(#[resource] map: &Map, ecs: &mut SubWorld, commands: &mut Command
     // Find all entities with both point and chasing component
     let mut movers = <(Entity, &Point, &ChasingPlayer, &FieldOfView)>::query();
     // Get the player
-    let mut player = <(&Point, &Player)>::query();
+    let mut player = <(Entity, &Point, &Player)>::query();
     // Get all entities with point and health component
     let mut positions = <(Entity, &Point, &Health)>::query();
 
     // Extract player position and player idx from the player query tuple result
-    let player_pos = player.iter(ecs).nth(0).unwrap().0;
-    let player_idx = map_idx(player_pos.x, player_pos.y);
+    let (player_entity, player_pos) = player
+        .iter(ecs)
+        .map(|(entity, pos, _)| (*entity, pos))
+        .next()
+        .unwrap();
+    let player_idx = map.map_idx(player_pos.x, player_pos.y);
+
+    // Nobody moves until the commands are flushed, so where everyone stands can be worked out once
+    let occupied: Vec<Point> = positions.iter(ecs).map(|(_, pos, _)| *pos).collect();
 
     // We use djikstra algorithm for pathfinding
     let search_targets = vec![player_idx];
-    let djikstra_map = DijkstraMap::new(SCREEN_WIDTH, SCREEN_HEIGHT, &search_targets, map, 1024.0);
+    let djikstra_map = DijkstraMap::new(map.width, map.height, &search_targets, map, 1024.0);
 
     movers.iter(ecs).for_each(|(entity, pos, _, fov)| {
-        // Check visibility to player
-        if !fov.visible_tiles.contains(&player_pos) {
-            // Did not see player
+        let statuses = ecs
+            .entry_ref(*entity)
+            .unwrap()
+            .get_component::<Statuses>()
+            .cloned()
+            .unwrap_or_default();
+        if sits_out_turn(&statuses) {
             return;
         }
+        let hasted = statuses.has(StatusKind::Hasted);
 
-        // Check every other
+        // Check every other, hasted monsters don't wait around
         if let Ok(entity_ref) = ecs.entry_ref(*entity) {
             if let Ok(move_every_other) = entity_ref.get_component::<MoveEveryOther>() {
-                if move_every_other.0 {
+                if move_every_other.0 && !hasted {
+                    return;
+                }
+            }
+        }
+
+        // Confused monsters stumble around whether they see the player or not
+        let stumble = if statuses.has(StatusKind::Confused) {
+            Some(*pos + random_direction(rng))
+        } else {
+            None
+        };
+
+        // Check visibility to player
+        if stumble.is_none() && !fov.visible_tiles.contains(player_pos) {
+            // Did not see player
+            return;
+        }
+
+        // Shooters keep their distance and fire whenever they have a clear shot
+        if stumble.is_none() {
+            if let Ok(ranged) = ecs.entry_ref(*entity).unwrap().get_component::<Ranged>() {
+                let distance = DistanceAlg::Pythagoras.distance2d(*pos, *player_pos);
+                let clear_shot = aim(ecs, map, *entity, *player_pos, ranged.range)
+                    .is_ok_and(|path| path.last() == Some(player_pos));
+                if clear_shot && firearm(ecs, *entity).is_ok() {
+                    commands.push((
+                        (),
+                        WantsToAttack {
+                            attacker: *entity,
+                            victim: player_entity,
+                        },
+                    ));
+                    if ranged.cooldown > 0 {
+                        commands.add_component(*entity, Cooldown(ranged.cooldown));
+                    }
+                    if let Ok(name) = ecs.entry_ref(*entity).unwrap().get_component::<Name>() {
+                        EventLog::log(commands, format!("{} fires at the drone", name.0));
+                    }
+                    return;
+                }
+                // Too close for comfort, back off to wherever is furthest from the player
+                if distance < KEEP_DISTANCE {
+                    let retreat = [
+                        Point::new(-1, 0),
+                        Point::new(1, 0),
+                        Point::new(0, -1),
+                        Point::new(0, 1),
+                    ]
+                    .iter()
+                    .map(|delta| *pos + *delta)
+                    .filter(|step| map.can_enter_tile(*step) && !occupied.contains(step))
+                    .max_by(|a, b| {
+                        let a = DistanceAlg::Pythagoras.distance2d(*a, *player_pos);
+                        let b = DistanceAlg::Pythagoras.distance2d(*b, *player_pos);
+                        a.partial_cmp(&b).unwrap()
+                    });
+                    if let Some(destination) = retreat {
+                        commands.push((
+                            (),
+                            WantsToMove {
+                                entity: *entity,
+                                destination,
+                            },
+                        ));
+                        return;
+                    }
+                }
+                // Reloading with the player in its sights, no need to get any closer
+                if clear_shot {
                     return;
                 }
             }
         }
 
-        let idx = map_idx(pos.x, pos.y);
+        let idx = map.map_idx(pos.x, pos.y);
         // Gets the lowest cost tile pointing towards the player
         if let Some(desination) = DijkstraMap::find_lowest_exit(&djikstra_map, idx, map) {
             // Calculate the distance to the player
             let distance = DistanceAlg::Pythagoras.distance2d(*pos, *player_pos);
             // Either get the player position or get a new tile to move to depending on distance
             // Helper: sqrt(2) > 1.2 so... figure that out...
-            let destination = if distance > 1.2 {
+            let mut destination = if distance > 1.2 {
                 map.index_to_point2d(desination)
             } else {
                 *player_pos
             };
 
+            // Hasted monsters run one more tile, as long as nobody is in the way of the first one
+            // A closed door on the first one takes the whole turn to open, same as for everyone else
+            let next_to_player = DistanceAlg::Pythagoras.distance2d(destination, *player_pos) < 1.2;
+            if hasted
+                && !next_to_player
+                && !map.is_closed_door(destination)
+                && !positions
+                    .iter(ecs)
+                    .any(|(_, target_pos, _)| *target_pos == destination)
+            {
+                if let Some(second) = DijkstraMap::find_lowest_exit(&djikstra_map, desination, map)
+                {
+                    destination = map.index_to_point2d(second);
+                }
+            }
+            let destination = stumble.unwrap_or(destination);
+
             // Same as random move component from here pretty much!
             let mut attacked = false;
             // Go through all "hittable" entities to check if it's a player or blocked
@@ -1,14 +1,29 @@
 use crate::prelude::*;
 
+// However thick the armour, a hit with any strength behind it still does this much
+const MINIMUM_DAMAGE: i32 = 1;
+// What a d20 plus accuracy minus evasion has to reach for an attack to land, 3 in 4 attacks between equals
+const TO_HIT: i32 = 6;
+
 #[system]
 #[read_component(WantsToAttack)]
 #[read_component(Player)]
 #[write_component(Health)]
 #[read_component(Damage)]
+#[read_component(Defence)]
+#[read_component(Accuracy)]
+#[read_component(Evasion)]
 #[read_component(Carried)]
-#[read_component(Stunned)]
+#[read_component(Statuses)]
+#[read_component(OnHit)]
 #[read_component(Name)]
-pub fn combat(ecs: &mut SubWorld, commands: &mut CommandBuffer) {
+#[read_component(GrantsXp)]
+#[write_component(Progress)]
+pub fn combat(
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] rng: &mut RandomNumberGenerator,
+) {
     // Get our list of attackers
     let mut attackers = <(Entity, &WantsToAttack)>::query();
     let victims: Vec<(Entity, Entity, Entity)> = attackers
@@ -19,32 +34,33 @@ pub fn combat(ecs: &mut SubWorld, commands: &mut CommandBuffer) {
     // We don't mofidy the attack victim inside the query since that could lead to borrow checker problems
     // The rust way is usually to obtain the list of data and then modify in a seperate loop
     victims.iter().for_each(|(message, attacker, victim)| {
-        // Does the attacker have a stunned component?
-        let is_attacker_stunned = ecs
-            .entry_ref(*attacker)
-            .unwrap()
-            .get_component::<Stunned>()
-            .is_ok();
+        // Is the attacker stunned?
+        let is_attacker_stunned = is_stunned(ecs, *attacker);
 
         if !is_attacker_stunned {
-            // Calculate the base damage from our attacker
-            let base_damage = if let Ok(v) = ecs.entry_ref(*attacker) {
-                if let Ok(dmg) = v.get_component::<Damage>() {
-                    dmg.0
-                } else {
-                    0
-                }
+            // Accuracy against evasion decides whether the attack lands at all, a natural 20 always does
+            // and hits twice as hard
+            let hit_roll = rng.roll_dice(1, 20);
+            let accuracy: i32 = stat::<Accuracy>(ecs, *attacker).iter().map(|a| a.0).sum();
+            let evasion: i32 = stat::<Evasion>(ecs, *victim).iter().map(|e| e.0).sum();
+            let critical = hit_roll == 20;
+            let hit = critical || hit_roll + accuracy - evasion >= TO_HIT;
+
+            // Roll the attacker's own damage and the damage of the weapon it carries
+            let rolls = if critical { 2 } else { 1 };
+            let attack: i32 = stat::<Damage>(ecs, *attacker)
+                .iter()
+                .map(|damage| (0..rolls).map(|_| rng.roll(damage.0)).sum::<i32>())
+                .sum();
+
+            // The victim's own defence plus the armour it is wearing
+            let defence: i32 = stat::<Defence>(ecs, *victim).iter().map(|d| d.0).sum();
+            let final_damage = if hit && attack > 0 {
+                i32::max(attack - defence, MINIMUM_DAMAGE)
             } else {
                 0
             };
-
-            // get weapon damage from attacker
-            // Query for all Carried + Damage components and then sum them up for total weapon damage on our holder
-            let weapon_damage: i32 = <(&Carried, &Damage)>::query()
-                .iter(ecs)
-                .filter(|(carried, _)| carried.0 == *attacker)
-                .map(|(_, dmg)| dmg.0)
-                .sum();
+            let blocked = if hit { attack - final_damage } else { 0 };
 
             let victim_is_player = ecs
                 .entry_ref(*victim)
@@ -62,32 +78,107 @@ pub fn combat(ecs: &mut SubWorld, commands: &mut CommandBuffer) {
             // Log it to event
             if attacker_is_player {
                 if let Ok(name) = ecs.entry_ref(*victim).unwrap().get_component::<Name>() {
+                    let message = match (hit, critical) {
+                        (false, _) => format!("Player missed {}", name.0),
+                        (true, false) => {
+                            format!("Player attacked {} for {} damage", name.0, final_damage)
+                        }
+                        (true, true) => {
+                            format!(
+                                "Critical! Player attacked {} for {} damage",
+                                name.0, final_damage
+                            )
+                        }
+                    };
                     EventLog::log(
                         commands,
-                        format!(
-                            "Player attacked {} for {} damage",
-                            name.0,
-                            base_damage + weapon_damage
-                        ),
+                        match blocked {
+                            0 => message,
+                            blocked => format!("{}, {} blocked", message, blocked),
+                        },
                     );
                 }
             }
+            // Monsters hitting the player aren't worth a line, unless something out of the ordinary happened
+            if victim_is_player {
+                if let Ok(name) = ecs.entry_ref(*attacker).unwrap().get_component::<Name>() {
+                    let message = match (hit, critical) {
+                        (false, _) => Some(format!("{} missed", name.0)),
+                        (true, true) => Some(format!(
+                            "{} hit critically for {} damage",
+                            name.0, final_damage
+                        )),
+                        (true, false) if blocked > 0 => Some(format!(
+                            "Blocked {} of {}'s {} damage",
+                            blocked, name.0, attack
+                        )),
+                        (true, false) => None,
+                    };
+                    if let Some(message) = message {
+                        EventLog::log(commands, message);
+                    }
+                }
+            }
+
+            // A miss is all there is to it
+            if !hit {
+                commands.remove(*message);
+                return;
+            }
 
             // Does our victim have a Health component
-            if let Ok(mut health) = ecs
+            let mut killed = false;
+            if let Ok(health) = ecs
                 .entry_mut(*victim)
                 .unwrap()
                 .get_component_mut::<Health>()
             {
-                let final_damage = base_damage + weapon_damage;
-
                 health.current -= final_damage;
-                if health.current < 1 && !victim_is_player {
-                    commands.remove(*victim);
-                }
+                killed = health.current < 1 && !victim_is_player;
+            }
+            if killed {
+                commands.remove(*victim);
+                grant_xp(ecs, commands, *attacker, *victim);
+            }
+
+            // Whatever else comes with the hit, from the attacker itself or the weapon it carries
+            let mut on_hit: Vec<Effect> = Vec::new();
+            if let Ok(effects) = ecs.entry_ref(*attacker).unwrap().get_component::<OnHit>() {
+                on_hit.extend(effects.0.iter());
             }
+            <(&Carried, &OnHit)>::query()
+                .iter(ecs)
+                .filter(|(carried, _)| carried.0 == *attacker)
+                .for_each(|(_, effects)| on_hit.extend(effects.0.iter()));
+            on_hit.iter().for_each(|effect| {
+                commands.push((
+                    (),
+                    ApplyEffect {
+                        user: *attacker,
+                        victim: Some(*victim),
+                        target: None,
+                        effect: *effect,
+                    },
+                ));
+            });
         }
 
         commands.remove(*message);
     });
 }
+
+// A stat of the creature itself along with what its weapon and armour add to it
+fn stat<T: legion::storage::Component + Copy>(ecs: &SubWorld, entity: Entity) -> Vec<T> {
+    let own = ecs
+        .entry_ref(entity)
+        .ok()
+        .and_then(|entry| entry.get_component::<T>().ok().copied());
+    own.into_iter()
+        .chain(
+            <(&Carried, &T)>::query()
+                .iter(ecs)
+                .filter(|(carried, _)| carried.0 == entity)
+                .map(|(_, stat)| *stat),
+        )
+        .collect()
+}
@@ -0,0 +1,15 @@
+use crate::prelude::*;
+
+// Ranged monsters and weapons get a turn closer to their next shot at the end of every monster turn
+#[system]
+#[write_component(Cooldown)]
+pub fn cooldowns(ecs: &mut SubWorld, commands: &mut CommandBuffer) {
+    <(Entity, &mut Cooldown)>::query()
+        .iter_mut(ecs)
+        .for_each(|(entity, cooldown)| {
+            cooldown.0 -= 1;
+            if cooldown.0 < 1 {
+                commands.remove_component::<Cooldown>(*entity);
+            }
+        });
+}
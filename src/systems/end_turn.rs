@@ -6,18 +6,32 @@ use crate::prelude::*;
 #[read_component(Player)]
 #[read_component(Point)]
 #[read_component(TelerportationCrystal)]
-pub fn end_turn(ecs: &SubWorld, #[resource] turn_state: &mut TurnState, #[resource] map: &Map) {
+#[write_component(Statuses)]
+#[read_component(Progress)]
+pub fn end_turn(ecs: &mut SubWorld, #[resource] turn_state: &mut TurnState, #[resource] map: &Map) {
     // Get the teleportation crystal point
     let mut crystal = <&Point>::query().filter(component::<TelerportationCrystal>());
     let crystal_default = Point::new(-1, -1);
     // Unwrap-or will not crash like unwrap but will return None if it fails
     // The argument to unwrap_or is the default that will be used if None is returned
-    let teleportation_pos = crystal.iter(ecs).nth(0).unwrap_or(&crystal_default);
+    let teleportation_pos = *crystal.iter(ecs).nth(0).unwrap_or(&crystal_default);
+
+    let player_statuses = <&Statuses>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .next()
+        .cloned()
+        .unwrap_or_default();
 
     let current_state = turn_state.clone();
     let mut new_state = match turn_state {
         TurnState::AwaitingInput => return,
+        // Hasted, the monsters miss out on every other turn. The status only counts down on monster turns
+        // so its phase keeps track of whether the extra turn was taken already
+        TurnState::PlayerTurn if extra_turn(ecs) => TurnState::AwaitingInput,
         TurnState::PlayerTurn => TurnState::MonsterTurn,
+        // Slowed, the monsters get a second go while the player sits out
+        TurnState::MonsterTurn if sits_out_turn(&player_statuses) => TurnState::MonsterTurn,
         TurnState::MonsterTurn => TurnState::AwaitingInput,
         _ => current_state,
     };
@@ -29,14 +43,45 @@ pub fn end_turn(ecs: &SubWorld, #[resource] turn_state: &mut TurnState, #[resour
         if hp.current < 1 {
             new_state = TurnState::GameOver;
         }
-        if pos == teleportation_pos {
+        if *pos == teleportation_pos {
             new_state = TurnState::Victory;
         }
         let idx = map.point2d_to_index(*pos);
         if map.tiles[idx] == TileType::Exit {
             new_state = TurnState::NextLevel;
         }
+        if map.tiles[idx] == TileType::StairsUp {
+            new_state = TurnState::PreviousLevel;
+        }
     });
 
+    // Enough experience for a new level, the player picks what gets better before playing on
+    let can_level_up = <&Progress>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .any(|progress| progress.can_level_up());
+    if new_state == TurnState::AwaitingInput && can_level_up {
+        new_state = TurnState::LevelUp;
+    }
+
     *turn_state = new_state;
 }
+
+// Flips the phase of the player's haste, true when the turn just played was the first of the two
+fn extra_turn(ecs: &mut SubWorld) -> bool {
+    <&mut Statuses>::query()
+        .filter(component::<Player>())
+        .iter_mut(ecs)
+        .filter_map(|statuses| {
+            statuses
+                .0
+                .iter_mut()
+                .find(|status| status.kind == StatusKind::Hasted)
+        })
+        .map(|haste| {
+            haste.phase = !haste.phase;
+            haste.phase
+        })
+        .next()
+        .unwrap_or(false)
+}
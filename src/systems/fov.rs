@@ -3,15 +3,19 @@ use crate::prelude::*;
 #[system]
 #[read_component(Point)]
 #[write_component(FieldOfView)]
+#[read_component(Statuses)]
 pub fn fov(ecs: &mut SubWorld, #[resource] map: &Map) {
     // let mut views = <(&Point, &mut FieldOfView)>::query();
     // Go through each component that has field of view & point and set fov.visible_tiles to something according
     // Our Map has implemented Algorithm2D so we can use field_of_view_set to get a HashSet of visible tiles
-    <(&Point, &mut FieldOfView)>::query()
+    <(&Point, &mut FieldOfView, Option<&Statuses>)>::query()
         .iter_mut(ecs)
-        .filter(|(_, fov)| fov.is_dirty)
-        .for_each(|(pos, mut fov)| {
-            fov.visible_tiles = field_of_view_set(*pos, fov.radius, map);
+        .filter(|(_, fov, _)| fov.is_dirty)
+        .for_each(|(pos, fov, statuses)| {
+            // Blinded only sees the tiles right next to it
+            let blinded = statuses.is_some_and(|s| s.has(StatusKind::Blinded));
+            let radius = if blinded { 1 } else { fov.radius };
+            fov.visible_tiles = field_of_view_set(*pos, radius, map);
             fov.is_dirty = false;
         });
 }
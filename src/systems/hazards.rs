@@ -0,0 +1,34 @@
+use crate::prelude::*;
+
+// Whoever ends their move on top of a hazard gets its effects, every turn they stay there
+// The player only trips them on their own turn and the monsters on theirs
+#[system]
+#[read_component(Point)]
+#[read_component(Provides)]
+#[read_component(Health)]
+#[read_component(Player)]
+pub fn hazards(ecs: &SubWorld, commands: &mut CommandBuffer, #[resource] turn_state: &TurnState) {
+    let players_turn = *turn_state == TurnState::PlayerTurn;
+    let mut creatures = <(Entity, &Point, Option<&Player>)>::query().filter(component::<Health>());
+    <(&Point, &Provides)>::query()
+        .filter(component::<Hazard>())
+        .iter(ecs)
+        .for_each(|(hazard_pos, provides)| {
+            creatures
+                .iter(ecs)
+                .filter(|(_, pos, player)| *pos == hazard_pos && player.is_some() == players_turn)
+                .for_each(|(entity, _, _)| {
+                    provides.0.iter().for_each(|effect| {
+                        commands.push((
+                            (),
+                            ApplyEffect {
+                                user: *entity,
+                                victim: None,
+                                target: None,
+                                effect: *effect,
+                            },
+                        ));
+                    });
+                });
+        });
+}
@@ -6,7 +6,16 @@ use crate::prelude::*;
 #[read_component(Item)]
 #[read_component(Carried)]
 #[read_component(Name)]
-pub fn hud(ecs: &SubWorld, #[resource] event_log: &mut EventLog) {
+#[read_component(Defence)]
+#[read_component(Progress)]
+#[read_component(Cooldown)]
+#[read_component(Ammo)]
+pub fn hud(
+    ecs: &SubWorld,
+    #[resource] event_log: &mut EventLog,
+    #[resource] seed: &RunSeed,
+    #[resource] campaign: &Campaign,
+) {
     // Query time!
     let mut health_query = <&Health>::query().filter(component::<Player>());
     let player_health = health_query.iter(ecs).nth(0).unwrap();
@@ -18,7 +27,7 @@ pub fn hud(ecs: &SubWorld, #[resource] event_log: &mut EventLog) {
     // Instructional text
     draw_batch.print_centered(
         2,
-        "Cursor keys to move. G for pickup. 1-9 for item use", // .to_ascii_uppercase(),
+        "Cursor keys to move. G for pickup. 1-9 for item use. F to fire. I for inventory", // .to_ascii_uppercase(),
     );
     // Draw health bar
     draw_batch.bar_horizontal(
@@ -34,6 +43,31 @@ pub fn hud(ecs: &SubWorld, #[resource] event_log: &mut EventLog) {
         ColorPair::new(WHITE, RED3),
     );
 
+    // Experience bar right under the health bar, narrower so there is room on both sides of it
+    if let Some(progress) = <&Progress>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .next()
+    {
+        draw_batch.bar_horizontal(
+            Point::new(SCREEN_WIDTH / 2, 1),
+            SCREEN_WIDTH,
+            progress.xp,
+            progress.xp_to_next_level(),
+            ColorPair::new(GOLD, BLACK),
+        );
+        draw_batch.print_color_centered(
+            1,
+            format!(
+                " DRONE LEVEL {}  XP {} / {} ",
+                progress.level,
+                progress.xp,
+                progress.xp_to_next_level()
+            ),
+            ColorPair::new(BLACK, GOLD),
+        );
+    }
+
     let (player, map_level, wait_count) = <(Entity, &Player)>::query()
         .iter(ecs)
         .find_map(|(entity, player)| Some((*entity, player.map_level, player.wait_count)))
@@ -45,33 +79,60 @@ pub fn hud(ecs: &SubWorld, #[resource] event_log: &mut EventLog) {
         format!("Level: {}", map_level + 1),
         ColorPair::new(YELLOW, BLACK),
     );
-    let theme = match map_level {
-        0 | 1 => "FOREST",
-        2 | 3 => "CAVE",
-        _ => "THE DUNGEON",
-    };
     draw_batch.print_color_right(
         Point::new(SCREEN_WIDTH * 2, 2),
-        format!("{}", theme),
+        &campaign.level(map_level).name,
+        ColorPair::new(YELLOW, BLACK),
+    );
+    // Show the seed so a run can be shared and replayed with --seed
+    draw_batch.print_color_right(
+        Point::new(SCREEN_WIDTH * 2, 3),
+        format!("Seed: {}", seed.0),
         ColorPair::new(YELLOW, BLACK),
     );
 
     // Items HUD
-    let mut item_query = <(&Item, &Name, &Carried)>::query();
+    let mut item_query = <(Entity, &Item, &Name, &Carried)>::query();
     let mut y = 4;
     // Draw the name for each item in the player's inventory
     item_query
         .iter(ecs)
         // Only grab the Carried components that are carried by the Player entity
-        .filter(|(_, _, carried)| carried.0 == player)
-        .for_each(|(_, name, _)| {
-            draw_batch.print(Point::new(3, y), format!("{} : {}", y - 3, &name.0));
+        .filter(|(_, _, _, carried)| carried.0 == player)
+        .for_each(|(entity, _, name, _)| {
+            // Ranged weapons tell how many shots are left or when they can fire again
+            let entry = ecs.entry_ref(*entity).unwrap();
+            let status = match (
+                entry.get_component::<Cooldown>(),
+                entry.get_component::<Ammo>(),
+            ) {
+                (Ok(cooldown), _) => format!(" (ready in {})", cooldown.0),
+                (_, Ok(ammo)) => format!(" ({} shots)", ammo.0),
+                _ => "".to_string(),
+            };
+            draw_batch.print(
+                Point::new(3, y),
+                format!("{} : {}{}", y - 3, &name.0, status),
+            );
             y += 1;
         });
     // Draw text only if we have an item
     if y > 4 {
         draw_batch.print_color(Point::new(3, 3), "Inventory", ColorPair::new(YELLOW, BLACK));
     }
+    // Defence of the drone and whatever armour it is wearing
+    let defence: i32 = <(&Defence, Option<&Carried>, Option<&Player>)>::query()
+        .iter(ecs)
+        .filter(|(_, carried, is_player)| {
+            is_player.is_some() || carried.is_some_and(|carried| carried.0 == player)
+        })
+        .map(|(defence, _, _)| defence.0)
+        .sum();
+    draw_batch.print_color(
+        Point::new(3, 1),
+        format!("Defence: {}", defence),
+        ColorPair::new(YELLOW, BLACK),
+    );
     // Draw how many waits the player have left
     draw_batch.print_color(
         Point::new(3, 2),
@@ -11,7 +11,7 @@ pub fn map_render(
 ) {
     // Get the player's field of view component
     let mut fov = <&FieldOfView>::query().filter(component::<Player>());
-    let player_fov = fov.iter(ecs).nth(0).unwrap();
+    let player_fov = fov.iter(ecs).next().unwrap();
     // Start a draw batch
     let mut draw_batch = DrawBatch::new();
     draw_batch.target(0);
@@ -20,21 +20,19 @@ pub fn map_render(
             let pt = Point::new(x, y);
             let offset = Point::new(camera.left_x, camera.top_y);
             // Also, check if player fov HashSet has this point i.e is it visible?
-            let idx = map_idx(pt.x, pt.y);
-            if map.in_bounds(pt)
-                && (player_fov.visible_tiles.contains(&pt) | map.revealed_tiles[idx])
+            // Also make sure the camera hasn't scrolled past the edge of the map
+            if let Some(idx) = map
+                .try_idx(pt)
+                .filter(|idx| player_fov.visible_tiles.contains(&pt) | map.revealed_tiles[*idx])
             {
-                let tint = if player_fov.visible_tiles.contains(&pt) {
-                    WHITE
-                } else {
-                    DARK_GRAY
-                };
-                // Go from x,y to tile index with this helper function
-                let idx = map_idx(x, y);
-                // Convert idx to glyph through or theme resource
-                let glyph = theme.tile_to_render(map.tiles[idx]);
+                // Convert idx to glyph and colours through or theme resource
+                let (glyph, mut color) = theme.tile_to_render(map.tiles[idx], idx);
+                // Tiles we only remember lose their colour and fade a bit
+                if !player_fov.visible_tiles.contains(&pt) {
+                    color.fg = color.fg.to_greyscale().lerp(RGBA::named(BLACK), 0.34);
+                }
                 // Render to the batch
-                draw_batch.set(pt - offset, ColorPair::new(tint, BLACK), glyph);
+                draw_batch.set(pt - offset, color, glyph);
             }
         }
     }
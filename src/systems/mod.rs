@@ -1,18 +1,22 @@
 use crate::prelude::*;
 
+mod apply_effects;
 mod chasing;
 mod combat;
+mod cooldowns;
 mod end_turn;
 mod entity_render;
 mod event;
 mod fov;
+mod hazards;
 mod hud;
 mod map_render;
 mod movement;
 mod player_input;
 mod random_move;
+mod statuses;
+mod target_render;
 mod tooltip;
-mod use_effect;
 mod use_item;
 
 // Dividing the Scheduler
@@ -23,18 +27,15 @@ mod use_item;
 */
 
 // A schedule - execution plan for our systems
+// None of the game logic schedules draw anything, that is left to the render schedule
+// so the game can run without a window (see Simulation)
 
 // While awaiting input
-// the screen still needs to display the map and entities. It also calls the player_input system.
+// It only calls the player_input system and makes sure the field of view is up to date.
 pub fn build_input_scheduler() -> Schedule {
     Schedule::builder()
         .add_system(player_input::player_input_system())
         .add_system(fov::fov_system())
-        .flush()
-        .add_system(map_render::map_render_system())
-        .add_system(entity_render::entity_render_system())
-        .add_system(hud::hud_system())
-        .add_system(tooltip::tooltips_system())
         .build()
 }
 
@@ -45,12 +46,14 @@ pub fn build_player_scheduler() -> Schedule {
         .flush()
         .add_system(movement::movement_system())
         .flush()
+        // Hazards go by where everyone ended up, their effects land on the same turn
+        .add_system(hazards::hazards_system())
+        .flush()
+        .add_system(apply_effects::apply_effects_system())
+        .flush()
         .add_system(fov::fov_system())
         .flush()
         .add_system(event::event_system())
-        .add_system(map_render::map_render_system())
-        .add_system(entity_render::entity_render_system())
-        .add_system(hud::hud_system())
         .add_system(end_turn::end_turn_system())
         .build()
 }
@@ -66,13 +69,73 @@ pub fn build_monster_scheduler() -> Schedule {
         .flush()
         .add_system(movement::movement_system())
         .flush()
+        // Hazards go by where everyone ended up, their effects land on the same turn
+        .add_system(hazards::hazards_system())
+        .flush()
+        .add_system(apply_effects::apply_effects_system())
+        .flush()
+        // Before the event log takes in this turn's messages, so what the statuses did shows up right away
+        // and someone a status finished off is gone before end_turn looks
+        .add_system(statuses::statuses_system())
+        .add_system(cooldowns::cooldowns_system())
+        .flush()
         .add_system(fov::fov_system())
         .flush()
         .add_system(event::event_system())
+        .add_system(end_turn::end_turn_system())
+        .build()
+}
+
+// Stunned creatures can neither move nor attack, any system looking at this needs to read Statuses
+pub fn is_stunned(ecs: &SubWorld, entity: Entity) -> bool {
+    ecs.entry_ref(entity).is_ok_and(|entry| {
+        entry
+            .get_component::<Statuses>()
+            .is_ok_and(|statuses| statuses.has(StatusKind::Stunned))
+    })
+}
+
+// Hands the experience for a kill to whoever made it, if they are the kind to care about it
+pub fn grant_xp(ecs: &mut SubWorld, commands: &mut CommandBuffer, killer: Entity, killed: Entity) {
+    let xp = match ecs
+        .entry_ref(killed)
+        .map(|entry| entry.get_component::<GrantsXp>().copied())
+    {
+        Ok(Ok(xp)) => xp.0,
+        _ => return,
+    };
+    if let Ok(mut entry) = ecs.entry_mut(killer) {
+        if let Ok(progress) = entry.get_component_mut::<Progress>() {
+            progress.xp += xp;
+            EventLog::log(commands, format!("Gained {} xp", xp));
+        }
+    }
+}
+
+// Slowed creatures only get to move when they have an even number of turns left on it
+pub fn sits_out_turn(statuses: &Statuses) -> bool {
+    statuses
+        .get(StatusKind::Slowed)
+        .is_some_and(|slowed| slowed.turns % 2 == 1)
+}
+
+// One step in any of the four directions, for whoever can't decide where they are going
+pub fn random_direction(rng: &mut RandomNumberGenerator) -> Point {
+    match rng.range(0, 4) {
+        0 => Point::new(-1, 0),
+        1 => Point::new(1, 0),
+        2 => Point::new(0, -1),
+        _ => Point::new(0, 1),
+    }
+}
+
+// Draws the map, entities and HUD, executed once every frame while we are in game
+pub fn build_render_scheduler() -> Schedule {
+    Schedule::builder()
         .add_system(map_render::map_render_system())
         .add_system(entity_render::entity_render_system())
         .add_system(hud::hud_system())
-        .add_system(end_turn::end_turn_system())
-        .add_system(use_effect::use_items_system())
+        .add_system(tooltip::tooltips_system())
+        .add_system(target_render::target_render_system())
         .build()
 }
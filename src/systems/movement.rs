@@ -2,7 +2,7 @@ use crate::prelude::*;
 
 #[system(for_each)]
 #[read_component(Player)]
-#[read_component(Stunned)]
+#[read_component(Statuses)]
 #[read_component(FieldOfView)]
 pub fn movement(
     entity: &Entity,
@@ -12,8 +12,19 @@ pub fn movement(
     ecs: &mut SubWorld,
     commands: &mut CommandBuffer,
 ) {
-    if map.can_enter_tile(want_move.destination) {
-        if let Ok(mut entry) = ecs.entry_mut(want_move.entity) {
+    let stunned = is_stunned(ecs, want_move.entity);
+    if map.is_closed_door(want_move.destination) {
+        // Walking into a closed door opens it, which is all you get to do this turn
+        if !stunned && ecs.entry_ref(want_move.entity).is_ok() {
+            let idx = map.map_idx(want_move.destination.x, want_move.destination.y);
+            map.tiles[idx] = TileType::DoorOpen;
+            // Everyone might be able to see a bit more now
+            <(Entity, &FieldOfView)>::query()
+                .iter(ecs)
+                .for_each(|(entity, fov)| commands.add_component(*entity, fov.clone_dirty()));
+        }
+    } else if map.can_enter_tile(want_move.destination) {
+        if let Ok(entry) = ecs.entry_mut(want_move.entity) {
             if let Ok(fov) = entry.get_component::<FieldOfView>() {
                 commands.add_component(want_move.entity, fov.clone_dirty());
 
@@ -21,13 +32,14 @@ pub fn movement(
                 if entry.get_component::<Player>().is_ok() {
                     camera.on_player_move(want_move.destination);
                     fov.visible_tiles.iter().for_each(|pos| {
-                        map.revealed_tiles[map_idx(pos.x, pos.y)] = true;
+                        let idx = map.map_idx(pos.x, pos.y);
+                        map.revealed_tiles[idx] = true;
                     });
                 }
             }
 
-            // If want_move.entity has a Stunned, don't move
-            if entry.get_component::<Stunned>().is_err() {
+            // If want_move.entity is stunned, don't move
+            if !stunned {
                 // By adding a new point we replace the existing one
                 // This is faster since it lets Legion delegate the command instead of us assigning the data ourselves
                 commands.add_component(want_move.entity, want_move.destination);
@@ -12,27 +12,49 @@ use crate::prelude::*;
 #[read_component(Item)]
 #[read_component(Carried)]
 #[read_component(Weapon)]
+#[read_component(Armour)]
+#[read_component(Statuses)]
+#[read_component(Name)]
+#[read_component(FieldOfView)]
+#[read_component(Ranged)]
+#[read_component(Cooldown)]
+#[write_component(Ammo)]
+#[read_component(Provides)]
 pub fn player_input(
     ecs: &mut SubWorld,
     commands: &mut CommandBuffer,
-    #[resource] key: &Option<VirtualKeyCode>,
+    #[resource] action: &Option<PlayerAction>,
     #[resource] map: &Map,
     #[resource] turn_state: &mut TurnState,
+    #[resource] replay: &mut Replay,
+    #[resource] rng: &mut RandomNumberGenerator,
 ) {
     let mut players = <(Entity, &Point)>::query().filter(component::<Player>());
     let mut did_something = false;
-    if let Some(key) = *key {
+    // Anything that rolled the dice has to be replayed, even when it didn't spend the turn
+    let mut rolled = false;
+    if let Some(action) = *action {
         // Get our movement vector
-        let delta = match key {
-            VirtualKeyCode::Left | VirtualKeyCode::A => Point::new(-1, 0),
-            VirtualKeyCode::Right | VirtualKeyCode::D => Point::new(1, 0),
-            VirtualKeyCode::Up | VirtualKeyCode::W => Point::new(0, -1),
-            VirtualKeyCode::Down | VirtualKeyCode::S => Point::new(0, 1),
+        let delta = match action {
+            PlayerAction::Move(delta) => {
+                // Confused, the drone goes wherever it feels like
+                let confused = <&Statuses>::query()
+                    .filter(component::<Player>())
+                    .iter(ecs)
+                    .any(|statuses| statuses.has(StatusKind::Confused));
+                if confused {
+                    rolled = true;
+                    random_direction(rng)
+                } else {
+                    delta
+                }
+            }
             // Picking up an item?
-            VirtualKeyCode::G => {
+            PlayerAction::PickUp => {
                 let (player, player_pos) = players
                     .iter(ecs)
-                    .find_map(|(entity, pos)| Some((*entity, *pos)))
+                    .map(|(entity, pos)| (*entity, *pos))
+                    .next()
                     .unwrap();
 
                 // Check how many items we already got on us, used in the filter when checking for item we're standing on
@@ -47,19 +69,29 @@ pub fn player_input(
                 items
                     .iter(ecs)
                     .filter(|(_entity, _item, &item_pos)| {
-                        (item_pos == player_pos && number_of_carried_components <= 9)
+                        item_pos == player_pos && number_of_carried_components <= 9
                     })
                     .for_each(|(entity, _item, _item_pos)| {
                         // Ocotupus-preventing code for multiple weapons on player
+                        // The old weapon is left where the new one was
                         if let Ok(e) = ecs.entry_ref(*entity) {
                             let res = e.get_component::<Weapon>();
                             if res.is_ok() {
-                                // Query for other weapons and remove
+                                // Query for other weapons and drop them
                                 <(Entity, &Carried, &Weapon)>::query()
                                     .iter(ecs)
                                     .filter(|(_, c, _)| c.0 == player)
                                     .for_each(|(entity, _c, _w)| {
-                                        commands.remove(*entity);
+                                        drop_item(*entity, player_pos, ecs, commands);
+                                    });
+                            }
+                            // Same goes for armour, there's only room for one suit
+                            if e.get_component::<Armour>().is_ok() {
+                                <(Entity, &Carried, &Armour)>::query()
+                                    .iter(ecs)
+                                    .filter(|(_, c, _)| c.0 == player)
+                                    .for_each(|(entity, _, _)| {
+                                        drop_item(*entity, player_pos, ecs, commands)
                                     });
                             }
                         }
@@ -72,24 +104,39 @@ pub fn player_input(
 
                 Point::new(0, 0)
             }
-            // Alot of ways to consume items
-            VirtualKeyCode::Key1 => use_item(0, ecs, commands, &mut did_something),
-            VirtualKeyCode::Key2 => use_item(1, ecs, commands, &mut did_something),
-            VirtualKeyCode::Key3 => use_item(2, ecs, commands, &mut did_something),
-            VirtualKeyCode::Key4 => use_item(3, ecs, commands, &mut did_something),
-            VirtualKeyCode::Key5 => use_item(4, ecs, commands, &mut did_something),
-            VirtualKeyCode::Key6 => use_item(5, ecs, commands, &mut did_something),
-            VirtualKeyCode::Key7 => use_item(6, ecs, commands, &mut did_something),
-            VirtualKeyCode::Key8 => use_item(7, ecs, commands, &mut did_something),
-            VirtualKeyCode::Key9 => use_item(8, ecs, commands, &mut did_something),
-
-            _ => Point::new(0, 0),
+            PlayerAction::UseItem(n) => use_item(n, None, ecs, commands, &mut did_something),
+            PlayerAction::UseItemAt(n, target) => {
+                let player = players.iter(ecs).map(|(entity, _)| *entity).next().unwrap();
+                if can_see(ecs, player, target) {
+                    use_item(n, Some(target), ecs, commands, &mut did_something)
+                } else {
+                    EventLog::log(commands, "Can't see there".to_string());
+                    Point::zero()
+                }
+            }
+            PlayerAction::Drop(n) => {
+                let (player, player_pos) = players
+                    .iter(ecs)
+                    .map(|(entity, pos)| (*entity, *pos))
+                    .next()
+                    .unwrap();
+                if let Some(item) = carried_items(ecs, player).get(n) {
+                    drop_item(*item, player_pos, ecs, commands);
+                    did_something = true;
+                }
+                Point::zero()
+            }
+            PlayerAction::Wait => Point::new(0, 0),
+            PlayerAction::Fire(target) => fire(target, ecs, commands, map, &mut did_something),
+            // Only means something on the level up screen, see Simulation::level_up
+            PlayerAction::LevelUp(_) => return,
         };
 
         // Grab player entity and destination
         let (player_entity, destination) = players
             .iter(ecs)
-            .find_map(|(entity, pos)| Some((*entity, *pos + delta)))
+            .map(|(entity, pos)| (*entity, *pos + delta))
+            .next()
             .unwrap();
         let mut enemies = <(Entity, &Point)>::query().filter(component::<Enemy>());
 
@@ -120,8 +167,8 @@ pub fn player_input(
                         destination,
                     },
                 ));
-                // Check if destination is a wall tile type
-                did_something = map.can_enter_tile(destination);
+                // Check if destination is a wall tile type, opening a door spends the turn as well
+                did_something = map.can_enter_tile(destination) || map.is_closed_door(destination);
             }
         }
 
@@ -131,7 +178,7 @@ pub fn player_input(
             *turn_state = TurnState::PlayerTurn;
         } else {
             // Check the wait counter on player
-            let mut player = <&mut Player>::query().iter_mut(ecs).nth(0).unwrap();
+            let player = <&mut Player>::query().iter_mut(ecs).next().unwrap();
             if player.wait_count > 0 {
                 *turn_state = TurnState::PlayerTurn;
                 player.wait_count -= 1;
@@ -139,11 +186,17 @@ pub fn player_input(
                 *turn_state = TurnState::AwaitingInput;
             }
         }
+
+        // Only actions that spent the turn or rolled the dice go into the replay, the rest didn't change anything
+        if *turn_state == TurnState::PlayerTurn || rolled {
+            replay.actions.push(action);
+        }
     }
 }
 
 fn use_item(
     n: usize,
+    target: Option<Point>,
     ecs: &mut SubWorld,
     commands: &mut CommandBuffer,
     did_something: &mut bool,
@@ -151,7 +204,8 @@ fn use_item(
     // Find the player entity
     let player_entity = <(Entity, &Player)>::query()
         .iter(ecs)
-        .find_map(|(entity, _)| Some(*entity))
+        .map(|(entity, _)| *entity)
+        .next()
         .unwrap();
 
     // Find the item at nth position in our inventory
@@ -160,15 +214,23 @@ fn use_item(
         .filter(|(_, _, carried)| carried.0 == player_entity)
         .enumerate()
         .filter(|(item_count, (_, _, _))| *item_count == n)
-        .find_map(|(_, (item_entity, _, _))| Some(*item_entity));
+        .map(|(_, (item_entity, _, _))| *item_entity)
+        .next();
+
+    // Items with an Area effect hit wherever a tile was picked for them, without one they'd go to waste
+    // The window starts targeting for those, anything else driving the game has to send UseItemAt
+    if item_entity.is_some() && target.is_none() && item_area(ecs, player_entity, n).is_some() {
+        EventLog::log(commands, "Pick a tile to use that on".to_string());
+        return Point::zero();
+    }
 
     if let Some(item_entity) = item_entity {
-        // Make sure item_entity is not a weapon
+        // Make sure item_entity is not a weapon or armour, those are worn instead of used up
         let mut is_weapon = false;
         *did_something = true;
         if let Ok(e) = ecs.entry_ref(item_entity) {
             let res = e.get_component::<Weapon>();
-            if res.is_ok() {
+            if res.is_ok() || e.get_component::<Armour>().is_ok() {
                 is_weapon = true;
             }
         }
@@ -179,6 +241,7 @@ fn use_item(
                 ActivateItem {
                     used_by: player_entity,
                     item: item_entity,
+                    target,
                 },
             ));
         }
@@ -186,3 +249,72 @@ fn use_item(
 
     Point::zero()
 }
+
+// Shoots the ranged weapon the player carries at the target, the first one in the line of fire takes the hit
+fn fire(
+    target: Point,
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    map: &Map,
+    did_something: &mut bool,
+) -> Point {
+    let player = <Entity>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .copied()
+        .next()
+        .unwrap();
+
+    let shot = firearm(ecs, player).and_then(|(gun, ranged)| {
+        aim(ecs, map, player, target, ranged.range).map(|path| (gun, ranged, path))
+    });
+    let (gun, ranged, path) = match shot {
+        Ok(shot) => shot,
+        Err(reason) => {
+            EventLog::log(commands, reason);
+            return Point::zero();
+        }
+    };
+    *did_something = true;
+
+    // One shot less and a while before the next one
+    if let Ok(mut entry) = ecs.entry_mut(gun) {
+        if let Ok(ammo) = entry.get_component_mut::<Ammo>() {
+            ammo.0 -= 1;
+        }
+    }
+    if ranged.cooldown > 0 {
+        commands.add_component(gun, Cooldown(ranged.cooldown));
+    }
+
+    let victim = path.last().and_then(|landed| {
+        <(Entity, &Point)>::query()
+            .filter(component::<Health>())
+            .iter(ecs)
+            .find(|(_, pos)| *pos == landed)
+            .map(|(entity, _)| *entity)
+    });
+    match victim {
+        Some(victim) => {
+            commands.push((
+                (),
+                WantsToAttack {
+                    attacker: player,
+                    victim,
+                },
+            ));
+        }
+        None => EventLog::log(commands, "The shot hits nothing".to_string()),
+    }
+
+    Point::zero()
+}
+
+// Leaves a carried item on the tile, the same as it was before it was picked up
+fn drop_item(item: Entity, pos: Point, ecs: &SubWorld, commands: &mut CommandBuffer) {
+    commands.remove_component::<Carried>(item);
+    commands.add_component(item, pos);
+    if let Ok(name) = ecs.entry_ref(item).unwrap().get_component::<Name>() {
+        EventLog::log(commands, format!("Dropped {}", name.0));
+    }
+}
@@ -5,20 +5,22 @@ use crate::prelude::*;
 #[read_component(MovingRandomly)]
 #[read_component(Health)]
 #[read_component(Player)]
-pub fn random_move(ecs: &mut SubWorld, commands: &mut CommandBuffer) {
+#[read_component(Statuses)]
+pub fn random_move(
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] rng: &mut RandomNumberGenerator,
+) {
     // Grab all potential targets
     let mut positions = <(Entity, &Point, &Health)>::query();
     // Grab all the entities we want to move and iterate over them
-    let mut movers = <(Entity, &Point, &MovingRandomly)>::query();
-    movers.iter(ecs).for_each(|(entity, pos, _)| {
-        let mut rng = RandomNumberGenerator::new();
+    let mut movers = <(Entity, &Point, &MovingRandomly, Option<&Statuses>)>::query();
+    movers.iter(ecs).for_each(|(entity, pos, _, statuses)| {
+        if statuses.is_some_and(sits_out_turn) {
+            return;
+        }
         // Calculate our random new position
-        let destination = match rng.range(0, 4) {
-            0 => Point::new(-1, 0),
-            1 => Point::new(1, 0),
-            2 => Point::new(0, -1),
-            _ => Point::new(0, 1),
-        } + *pos;
+        let destination = random_direction(rng) + *pos;
 
         let mut attacked = false;
         positions
@@ -0,0 +1,72 @@
+use crate::prelude::*;
+
+// Runs at the end of every monster turn, the lasting effects do their thing and count down
+#[system]
+#[write_component(Statuses)]
+#[write_component(Health)]
+#[read_component(Point)]
+#[read_component(Player)]
+#[read_component(FieldOfView)]
+#[read_component(Name)]
+pub fn statuses(ecs: &mut SubWorld, commands: &mut CommandBuffer) {
+    // Anyone without a Point is waiting on another level, their statuses wait along with them
+    let mut creatures = <(Entity, &mut Statuses, &mut Health, &Name, Option<&Player>)>::query()
+        .filter(component::<Point>());
+    let mut worn_off = Vec::new();
+    creatures
+        .iter_mut(ecs)
+        .for_each(|(entity, statuses, health, name, player)| {
+            statuses.0.iter_mut().for_each(|status| {
+                let damage = status.kind.damage_per_turn(status.stacks);
+                if damage != 0 {
+                    health.current = i32::min(health.max, health.current - damage);
+                }
+                // Monsters slowly dying is plain to see, the player losing health should be spelled out
+                if damage > 0 && player.is_some() {
+                    EventLog::log(
+                        commands,
+                        format!(
+                            "{} takes {} damage from being {}",
+                            name.0,
+                            damage,
+                            status.kind.name()
+                        ),
+                    );
+                }
+                status.turns -= 1;
+                if status.turns <= 0 {
+                    EventLog::log(
+                        commands,
+                        format!("{} is no longer {}", name.0, status.kind.name()),
+                    );
+                    worn_off.push((*entity, status.kind));
+                }
+            });
+            statuses.0.retain(|status| status.turns > 0);
+        });
+
+    // Out of health, the player is left for end_turn to deal with
+    let mut dying = <(Entity, &Health, &Name)>::query()
+        .filter(component::<Statuses>() & component::<Point>() & !component::<Player>());
+    dying
+        .iter(ecs)
+        .filter(|(_, health, _)| health.current < 1)
+        .for_each(|(entity, _, name)| {
+            commands.remove(*entity);
+            EventLog::log(commands, format!("{} succumbs", name.0));
+        });
+
+    // Someone who could only see the next tile can look around again
+    worn_off
+        .iter()
+        .filter(|(_, kind)| *kind == StatusKind::Blinded)
+        .for_each(|(entity, _)| {
+            if let Ok(fov) = ecs
+                .entry_ref(*entity)
+                .unwrap()
+                .get_component::<FieldOfView>()
+            {
+                commands.add_component(*entity, fov.clone_dirty());
+            }
+        });
+}
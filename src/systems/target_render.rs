@@ -0,0 +1,69 @@
+use crate::prelude::*;
+
+// While picking a tile, shows the way a shot would fly and where it lands, or the area an item would hit
+#[system]
+#[read_component(Point)]
+#[read_component(Health)]
+#[read_component(FieldOfView)]
+pub fn target_render(
+    ecs: &SubWorld,
+    #[resource] targeting: &Option<Targeting>,
+    #[resource] map: &Map,
+    #[resource] camera: &Camera,
+) {
+    let targeting = match targeting {
+        Some(targeting) => targeting,
+        None => return,
+    };
+    let offset = Point::new(camera.left_x, camera.top_y);
+
+    // The HUD console has four of its cells for every tile of the map
+    let mut draw_batch = DrawBatch::new();
+    draw_batch.target(2);
+    let tiles = targeting.tiles(ecs, map);
+    let marker = match targeting.aiming {
+        Aiming::Fire => YELLOW,
+        Aiming::Item(..) => ORANGE,
+    };
+    let color = match &tiles {
+        Ok(tiles) => {
+            tiles.iter().for_each(|pt| {
+                draw_batch.set(
+                    (*pt - offset) * 4 + Point::new(1, 1),
+                    ColorPair::new(marker, BLACK),
+                    to_cp437('*'),
+                );
+            });
+            // Something is in the way of the shot, it takes the hit instead
+            if targeting.aiming == Aiming::Fire && tiles.last() != Some(&targeting.cursor) {
+                ORANGE
+            } else {
+                GREEN
+            }
+        }
+        Err(_) => RED,
+    };
+    draw_batch.draw_hollow_box(
+        Rect::with_size(
+            (targeting.cursor.x - offset.x) * 4,
+            (targeting.cursor.y - offset.y) * 4,
+            3,
+            3,
+        ),
+        ColorPair::new(color, BLACK),
+    );
+
+    let help = match (tiles, targeting.aiming) {
+        (Ok(_), Aiming::Fire) => {
+            "F, Enter or click to fire. Tab for the next target. Escape to cancel".to_string()
+        }
+        (Ok(_), Aiming::Item(..)) => {
+            "F, Enter or click to use it here. Tab for the next target. Escape to cancel"
+                .to_string()
+        }
+        (Err(reason), _) => reason,
+    };
+    draw_batch.print_color_centered(SCREEN_HEIGHT * 2 - 16, help, ColorPair::new(color, BLACK));
+
+    draw_batch.submit(10200).expect("Batch error");
+}
@@ -6,7 +6,7 @@ use crate::prelude::*;
 #[read_component(Name)]
 #[read_component(Player)]
 #[read_component(FieldOfView)]
-#[read_component(Stunned)]
+#[read_component(Statuses)]
 pub fn tooltips(ecs: &SubWorld, #[resource] mouse_pos: &Point, #[resource] camera: &Camera) {
     // Player fov
     let mut fov = <&FieldOfView>::query().filter(component::<Player>());
@@ -35,13 +35,21 @@ pub fn tooltips(ecs: &SubWorld, #[resource] mouse_pos: &Point, #[resource] camer
                 } else {
                     name.0.clone()
                 };
-            let stunned =
-                if let Ok(stunned) = ecs.entry_ref(*entity).unwrap().get_component::<Stunned>() {
-                    format!("Stunned ({})", stunned.0)
+            // One line per status with the turns it has left, and the doses if there is more than one
+            let statuses =
+                if let Ok(statuses) = ecs.entry_ref(*entity).unwrap().get_component::<Statuses>() {
+                    statuses
+                        .0
+                        .iter()
+                        .map(|status| match status.stacks {
+                            1 => format!("{} ({})\n", status.kind.name(), status.turns),
+                            n => format!("{} x{} ({})\n", status.kind.name(), n, status.turns),
+                        })
+                        .collect()
                 } else {
                     "".to_string()
                 };
-            let res = display + &stunned;
+            let res = display + &statuses;
             draw_batch.print(screen_pos, res);
         });
 
@@ -0,0 +1,176 @@
+// Picking a tile while TurnState::Targeting, to fire at or to use an item on. The cursor moves with the keys
+// and the mouse. Only the action it ends in goes into the replay, so none of this has to be saved
+
+use crate::prelude::*;
+
+// What the picked tile is for
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aiming {
+    Fire, // The ranged weapon the shooter carries
+    // Inventory index of an item with an Area effect, everyone within radius of the tile is hit
+    Item(usize, i32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Targeting {
+    pub shooter: Entity,
+    pub cursor: Point,
+    pub range: i32,
+    pub aiming: Aiming,
+}
+
+impl Targeting {
+    // Starts out on the closest monster in reach, or on the shooter when there is nothing to shoot at
+    pub fn new(ecs: &World, shooter: Entity, range: i32, aiming: Aiming) -> Self {
+        let mut targeting = Self {
+            shooter,
+            cursor: Point::zero(),
+            range,
+            aiming,
+        };
+        targeting.cursor = match targeting.targets(ecs).first() {
+            Some(target) => *target,
+            None => position(ecs, shooter).unwrap_or_else(Point::zero),
+        };
+        targeting
+    }
+
+    // Monsters the shooter can see within range, closest first
+    pub fn targets(&self, ecs: &impl EntityStore) -> Vec<Point> {
+        let from = match position(ecs, self.shooter) {
+            Some(pos) => pos,
+            None => return Vec::new(),
+        };
+        let mut targets: Vec<Point> = <&Point>::query()
+            .filter(component::<Enemy>())
+            .iter(ecs)
+            .copied()
+            .filter(|pos| can_see(ecs, self.shooter, *pos))
+            .filter(|pos| DistanceAlg::Pythagoras.distance2d(from, *pos) <= self.range as f32)
+            .collect();
+        targets.sort_by(|a, b| {
+            let a = DistanceAlg::Pythagoras.distance2d(from, *a);
+            let b = DistanceAlg::Pythagoras.distance2d(from, *b);
+            a.partial_cmp(&b).unwrap()
+        });
+        targets
+    }
+
+    // Tab goes through the targets, back to the closest one after the last
+    pub fn next_target(&mut self, ecs: &World) {
+        let targets = self.targets(ecs);
+        let next = match targets.iter().position(|target| *target == self.cursor) {
+            Some(i) => targets.get(i + 1).or_else(|| targets.first()),
+            None => targets.first(),
+        };
+        if let Some(next) = next {
+            self.cursor = *next;
+        }
+    }
+
+    // The tiles the shot flies over or the area the item hits, Err tells why the cursor is no good
+    pub fn tiles(&self, ecs: &impl EntityStore, map: &Map) -> Result<Vec<Point>, String> {
+        match self.aiming {
+            Aiming::Fire => aim(ecs, map, self.shooter, self.cursor, self.range),
+            Aiming::Item(_, radius) => {
+                if !can_see(ecs, self.shooter, self.cursor) {
+                    return Err("Can't see there".to_string());
+                }
+                let cursor = self.cursor;
+                Ok((-radius..=radius)
+                    .flat_map(|y| (-radius..=radius).map(move |x| cursor + Point::new(x, y)))
+                    .filter(|pt| {
+                        DistanceAlg::Pythagoras.distance2d(self.cursor, *pt) <= radius as f32
+                    })
+                    .filter(|pt| can_see(ecs, self.shooter, *pt))
+                    .collect())
+            }
+        }
+    }
+
+    // What the player does with the tile once they're happy with it
+    pub fn action(&self) -> PlayerAction {
+        match self.aiming {
+            Aiming::Fire => PlayerAction::Fire(self.cursor),
+            Aiming::Item(n, _) => PlayerAction::UseItemAt(n, self.cursor),
+        }
+    }
+}
+
+// Radius of the Area effects of the nth item the user carries, None when it doesn't need a tile picked
+pub fn item_area(ecs: &impl EntityStore, user: Entity, n: usize) -> Option<i32> {
+    <(&Item, &Carried, Option<&Provides>)>::query()
+        .iter(ecs)
+        .filter(|(_, carried, _)| carried.0 == user)
+        .nth(n)
+        .and_then(|(_, _, provides)| provides)
+        .and_then(|provides| provides.0.iter().filter_map(|effect| effect.area()).max())
+}
+
+// What the shooter fires with, itself for ranged monsters or else the ranged weapon it carries
+// Err tells why it can't fire right now
+pub fn firearm(ecs: &impl EntityStore, shooter: Entity) -> Result<(Entity, Ranged), String> {
+    let own = ecs
+        .entry_ref(shooter)
+        .ok()
+        .and_then(|entry| entry.get_component::<Ranged>().ok().copied())
+        .map(|ranged| (shooter, ranged));
+    let (gun, ranged) = own
+        .or_else(|| {
+            <(Entity, &Carried, &Ranged)>::query()
+                .iter(ecs)
+                .find(|(_, carried, _)| carried.0 == shooter)
+                .map(|(entity, _, ranged)| (*entity, *ranged))
+        })
+        .ok_or_else(|| "Nothing to fire with".to_string())?;
+
+    let entry = ecs.entry_ref(gun).unwrap();
+    if let Ok(cooldown) = entry.get_component::<Cooldown>() {
+        return Err(format!("Still cooling down, {} turns to go", cooldown.0));
+    }
+    if entry.get_component::<Ammo>().is_ok_and(|ammo| ammo.0 < 1) {
+        return Err("Out of ammo".to_string());
+    }
+    Ok((gun, ranged))
+}
+
+// The tiles a shot at the target flies over, the last one is where it lands
+// Anything with health stands in the way, Err tells why the shot can't be taken
+pub fn aim(
+    ecs: &impl EntityStore,
+    map: &Map,
+    shooter: Entity,
+    target: Point,
+    range: i32,
+) -> Result<Vec<Point>, String> {
+    let from = position(ecs, shooter).ok_or_else(|| "Not on this level".to_string())?;
+    if target == from {
+        return Err("Pick something to fire at".to_string());
+    }
+    if !can_see(ecs, shooter, target) {
+        return Err("Can't see there".to_string());
+    }
+    if DistanceAlg::Pythagoras.distance2d(from, target) > range as f32 {
+        return Err("Out of range".to_string());
+    }
+    let occupied: Vec<Point> = <&Point>::query()
+        .filter(component::<Health>())
+        .iter(ecs)
+        .copied()
+        .collect();
+    Ok(map.line_of_fire(from, target, |pt| occupied.contains(&pt)))
+}
+
+fn position(ecs: &impl EntityStore, entity: Entity) -> Option<Point> {
+    ecs.entry_ref(entity)
+        .ok()
+        .and_then(|entry| entry.get_component::<Point>().ok().copied())
+}
+
+pub fn can_see(ecs: &impl EntityStore, entity: Entity, pos: Point) -> bool {
+    ecs.entry_ref(entity).is_ok_and(|entry| {
+        entry
+            .get_component::<FieldOfView>()
+            .is_ok_and(|fov| fov.visible_tiles.contains(&pos))
+    })
+}
@@ -0,0 +1,47 @@
+// Development helper, keeps an eye on template.ron so monsters and items can be tuned without restarting
+// Only used in debug builds, see State in main.rs
+
+use crate::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+pub struct TemplateWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl TemplateWatcher {
+    // Watches the modded template.ron if there is one, since that is the one the game reads,
+    // otherwise the one in resources we are working on
+    pub fn new() -> Self {
+        let modded = PathBuf::from("mods/template.ron");
+        let path = if modded.exists() {
+            modded
+        } else {
+            PathBuf::from("resources/template.ron")
+        };
+        let modified = Self::modified(&path);
+        Self { path, modified }
+    }
+
+    fn modified(path: &Path) -> Option<SystemTime> {
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    // The freshly parsed templates (or what is wrong with them) once the file has changed since we last looked
+    pub fn poll(&mut self) -> Option<Result<Templates, Vec<String>>> {
+        let modified = Self::modified(&self.path);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Some(match std::fs::read_to_string(&self.path) {
+            Ok(contents) => Templates::parse(&contents),
+            Err(e) => Err(vec![format!(
+                "Failed reading {}: {}",
+                self.path.display(),
+                e
+            )]),
+        })
+    }
+}
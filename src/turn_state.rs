@@ -1,4 +1,6 @@
-#[derive(Copy, Clone, Debug, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum TurnState {
     Menu,
     AwaitingInput,
@@ -7,4 +9,8 @@ pub enum TurnState {
     GameOver,
     Victory,
     NextLevel,
+    PreviousLevel,
+    LevelUp,   // Waiting for the player to pick what the drone gets better at
+    Targeting, // Picking a tile to fire at, see Targeting
+    Inventory, // Looking through what the drone carries
 }
@@ -0,0 +1,51 @@
+// Keeps the levels the player has left behind so walking back into one finds it just as it was
+// Only the maps live here, the entities of a stored level stay in the world with an OtherLevelPosition
+
+use std::collections::HashMap;
+
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
+struct StoredLevel {
+    map: Map,
+    theme: String, // Themes can't be stored as they are, we keep the name instead (see ThemeLibrary::theme)
+    // The stairs the player took out of the level, coming back always brings them in the same way
+    // Saves from before this was kept have none, change_level looks for the stairs on those
+    #[serde(default)]
+    left_from: Option<Point>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct VisitedLevels {
+    levels: HashMap<u32, StoredLevel>,
+}
+
+impl VisitedLevels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn store(&mut self, map_level: u32, map: Map, theme: &dyn MapTheme, left_from: Point) {
+        self.levels.insert(
+            map_level,
+            StoredLevel {
+                map,
+                theme: theme.name().to_string(),
+                left_from: Some(left_from),
+            },
+        );
+    }
+
+    // Hands back the map, theme and the stairs the player left by of a level we've been on before,
+    // None if it has never been built
+    pub fn take(
+        &mut self,
+        map_level: u32,
+        themes: &ThemeLibrary,
+    ) -> Option<(Map, Box<dyn MapTheme>, Option<Point>)> {
+        self.levels
+            .remove(&map_level)
+            .map(|level| (level.map, themes.theme(&level.theme), level.left_from))
+    }
+}